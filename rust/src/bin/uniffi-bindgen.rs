@@ -0,0 +1,4 @@
+// Generates the Kotlin/Swift bindings from the built library: see SDKs/android/README.md and SDKs/ios/README.md
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
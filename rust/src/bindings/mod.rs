@@ -0,0 +1,319 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::decode_versioned_spk_hex;
+use crate::error::{lock_unpoisoned, KisrError, KisrErrorCode};
+use crate::kisr::{self, FeeEstimate, Invite, KisrPayload, Output, SafeJsonTx, SelectionStrategy, TxGenerator, Utxo};
+use crate::tx::generator::parse_txid;
+
+// UniFFI interface next to the C ABI: the same `kisr` types, with records instead of pointers and JSON strings.
+// Nothing here returns memory the caller has to free, and failures arrive as typed exceptions.
+
+#[derive(Debug, uniffi::Error)]
+pub enum KisrFfiError {
+        // `code` is the KisrErrorCode value, `name` its camelCase name as in kaspa_last_error_json
+        Failed { code: i32, name: String, message: String, context_json: String },
+}
+
+impl fmt::Display for KisrFfiError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self { KisrFfiError::Failed { name, message, .. } => write!(f, "{}: {}", name, message) }
+        }
+}
+
+impl std::error::Error for KisrFfiError {}
+
+impl From<KisrError> for KisrFfiError {
+        fn from(err: KisrError) -> Self {
+                KisrFfiError::Failed {
+                        code: err.code.as_c_int(),
+                        name: err.code.name().to_string(),
+                        context_json: serde_json::Value::Object(err.context).to_string(),
+                        message: err.message,
+                }
+        }
+}
+
+type FfiResult<T> = Result<T, KisrFfiError>;
+
+// `script_public_key` carries the 4-hex-digit version prefix, as in SafeJSON
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct KisrUtxo {
+        pub transaction_id: String,
+        pub index: u32,
+        pub amount: u64,
+        pub script_public_key: String,
+        pub block_daa_score: u64,
+        pub is_coinbase: bool,
+}
+
+impl TryFrom<KisrUtxo> for Utxo {
+        type Error = KisrError;
+
+        fn try_from(u: KisrUtxo) -> Result<Self, KisrError> {
+                let txid = parse_txid(&u.transaction_id)?;
+                let (script_version, spk_bytes) = decode_versioned_spk_hex(&u.script_public_key)
+                        .map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, format!("invalid script public key for {}:{}", u.transaction_id, u.index))
+                                .with("transactionId", u.transaction_id.as_str()).with("index", u.index))?;
+                Ok(Utxo { txid, index: u.index, amount: u.amount, spk_bytes, script_version, block_daa_score: u.block_daa_score, is_coinbase: u.is_coinbase })
+        }
+}
+
+impl From<Utxo> for KisrUtxo {
+        fn from(u: Utxo) -> Self {
+                KisrUtxo {
+                        transaction_id: hex::encode(u.txid),
+                        index: u.index,
+                        amount: u.amount,
+                        script_public_key: format!("{:04x}{}", u.script_version, hex::encode(&u.spk_bytes)),
+                        block_daa_score: u.block_daa_score,
+                        is_coinbase: u.is_coinbase,
+                }
+        }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct KisrOutput {
+        pub address: String,
+        pub amount: u64,
+}
+
+impl From<KisrOutput> for Output {
+        fn from(o: KisrOutput) -> Self { Output { address: o.address, amount: o.amount } }
+}
+
+fn to_utxos(utxos: Vec<KisrUtxo>) -> Result<Vec<Utxo>, KisrError> {
+        utxos.into_iter().enumerate().map(|(i, u)| Utxo::try_from(u).map_err(|e| e.with("position", i))).collect()
+}
+
+#[derive(uniffi::Object)]
+pub struct KisrTxGenerator {
+        inner: Mutex<TxGenerator>,
+}
+
+impl KisrTxGenerator {
+        fn with<R>(&self, f: impl FnOnce(&mut TxGenerator) -> Result<R, KisrError>) -> FfiResult<R> {
+                f(&mut lock_unpoisoned(&self.inner)).map_err(KisrFfiError::from)
+        }
+
+        // Builds work on a copy so the lock is not held during selection and signing
+        fn snapshot(&self) -> TxGenerator { lock_unpoisoned(&self.inner).clone() }
+}
+
+#[uniffi::export]
+impl KisrTxGenerator {
+        // `network` as in kaspa_tx_generator_new_with_network
+        #[uniffi::constructor]
+        pub fn new(network: String) -> FfiResult<Arc<Self>> {
+                Ok(Arc::new(KisrTxGenerator { inner: Mutex::new(TxGenerator::for_network(&network)?) }))
+        }
+
+        pub fn clear(&self) {
+                lock_unpoisoned(&self.inner).clear();
+        }
+
+        pub fn set_change_address(&self, address: String) -> FfiResult<()> {
+                self.with(|gen| gen.change_address(&address).map(|_| ()))
+        }
+
+        pub fn set_fee_rate(&self, sompi_per_kilomass: i64) {
+                lock_unpoisoned(&self.inner).fee_rate(sompi_per_kilomass);
+        }
+
+        pub fn set_payload(&self, payload: Vec<u8>) {
+                lock_unpoisoned(&self.inner).payload(payload);
+        }
+
+        pub fn add_utxos(&self, utxos: Vec<KisrUtxo>) -> FfiResult<()> {
+                let utxos = to_utxos(utxos)?;
+                self.with(|gen| { for u in utxos { gen.add_utxo(u); } Ok(()) })
+        }
+
+        pub fn add_candidate_utxos(&self, utxos: Vec<KisrUtxo>) -> FfiResult<()> {
+                let utxos = to_utxos(utxos)?;
+                self.with(|gen| { for u in utxos { gen.add_candidate_utxo(u); } Ok(()) })
+        }
+
+        pub fn add_output(&self, output: KisrOutput) -> FfiResult<()> {
+                self.with(|gen| gen.add_output(&output.address, output.amount).map(|_| ()))
+        }
+
+        pub fn set_virtual_daa_score(&self, virtual_daa_score: u64) {
+                lock_unpoisoned(&self.inner).virtual_daa_score(virtual_daa_score);
+        }
+
+        pub fn set_selection_strategy(&self, strategy: SelectionStrategy) {
+                lock_unpoisoned(&self.inner).selection_strategy(strategy);
+        }
+
+        pub fn exclude_outpoint(&self, transaction_id: String, index: u32) -> FfiResult<()> {
+                let txid = parse_txid(&transaction_id)?;
+                self.with(|gen| { gen.exclude_outpoint(txid, index); Ok(()) })
+        }
+
+        pub fn set_input_sighash(&self, input_index: u32, sighash_type: u8) -> FfiResult<()> {
+                self.with(|gen| gen.input_sighash(input_index, sighash_type).map(|_| ()))
+        }
+
+        // None signs every input again
+        pub fn set_sign_indices(&self, indices: Option<Vec<u32>>) {
+                lock_unpoisoned(&self.inner).sign_indices(indices.map(|v| v.into_iter().collect()));
+        }
+
+        pub fn selected_utxos(&self) -> FfiResult<Vec<KisrUtxo>> {
+                Ok(self.snapshot().selected_utxos()?.into_iter().map(KisrUtxo::from).collect())
+        }
+
+        // Release with kaspa_outpoint_release or release_outpoint
+        pub fn reserve_selection(&self) -> FfiResult<Vec<KisrUtxo>> {
+                self.with(|gen| Ok(gen.reserve_selection()?.into_iter().map(KisrUtxo::from).collect()))
+        }
+
+        pub fn build_unsigned(&self) -> FfiResult<SafeJsonTx> {
+                Ok(self.snapshot().build_unsigned()?)
+        }
+
+        // `algo` is 0 (Schnorr) or 1 (ECDSA)
+        pub fn build_and_sign(&self, private_key_hex: String, sighash_type: u8, algo: u8) -> FfiResult<SafeJsonTx> {
+                Ok(self.snapshot().build_and_sign(&private_key_hex, sighash_type, algo)?)
+        }
+
+        pub fn build_and_sign_with_keys(&self, private_keys_hex: Vec<String>, sighash_type: u8) -> FfiResult<SafeJsonTx> {
+                Ok(self.snapshot().build_and_sign_with_keys(&private_keys_hex, sighash_type)?)
+        }
+}
+
+#[uniffi::export]
+pub fn release_outpoint(transaction_id: String, index: u32) -> FfiResult<bool> {
+        Ok(crate::tx::reserve::release(&(parse_txid(&transaction_id)?, index)))
+}
+
+#[uniffi::export]
+pub fn estimate_fee(network: String, utxos: Vec<KisrUtxo>, outputs: Vec<KisrOutput>, fee_rate: i64, payload: Vec<u8>) -> FfiResult<FeeEstimate> {
+        let network_id = kisr::network(&network)?;
+        let outputs: Vec<Output> = outputs.into_iter().map(Output::from).collect();
+        Ok(FeeEstimate::compute(network_id, &to_utxos(utxos)?, &outputs, fee_rate, &payload)?)
+}
+
+#[uniffi::export]
+pub fn generate_code() -> String { crate::code::generate() }
+
+#[uniffi::export]
+pub fn normalize_code(code: String) -> FfiResult<String> {
+        crate::code::normalize(&code).map_err(|e| KisrError::from(e).into())
+}
+
+// Returns the envelope bytes to carry in the anchor payload
+#[uniffi::export]
+pub fn seal_invite(invite: Invite) -> FfiResult<Vec<u8>> {
+        Ok(invite.seal()?.into_bytes())
+}
+
+#[uniffi::export]
+pub fn open_invite(code: String, envelope: Vec<u8>) -> FfiResult<Invite> {
+        Ok(Invite::open(&code, &kisr::Envelope::from_bytes(envelope))?)
+}
+
+#[uniffi::export]
+pub fn encode_payload(payload: KisrPayload) -> FfiResult<Vec<u8>> {
+        payload.encode().map_err(|e| KisrError::new(KisrErrorCode::InvalidPayload, e).into())
+}
+
+#[uniffi::export]
+pub fn decode_payload(tlv: Vec<u8>) -> FfiResult<KisrPayload> {
+        KisrPayload::decode(&tlv).map_err(|e| KisrError::new(KisrErrorCode::InvalidPayload, e).into())
+}
+
+#[cfg(feature = "rpc")]
+pub use self::rpc::*;
+
+#[cfg(feature = "rpc")]
+mod rpc {
+        use super::*;
+        use crate::kisr::{CreatedInvite, RedeemRequest};
+        use crate::rpc_ffi::{ClientInner, GlobalRt};
+        use crate::rpc_ffi::invite::{submit_safe, to_gen_utxo};
+        use kaspa_addresses::Address;
+        use kaspa_rpc_core::api::rpc::RpcApi;
+        use workflow_rpc::encoding::Encoding;
+
+        // Receives the KISR_STAGE_* values of kaspa_kisr_create_invite and kaspa_kisr_redeem_invite
+        #[uniffi::export(with_foreign)]
+        pub trait KisrProgressListener: Send + Sync {
+                fn on_stage(&self, stage: i32, detail: String);
+        }
+
+        fn report(listener: &Option<Arc<dyn KisrProgressListener>>) -> impl Fn(i32, &str) + Send + Sync + '_ {
+                move |stage, detail| if let Some(l) = listener { l.on_stage(stage, detail.to_string()) }
+        }
+
+        // The node work runs on the crate's runtime, so callers can await from any executor
+        async fn on_runtime<T: Send + 'static>(fut: impl std::future::Future<Output = Result<T, KisrError>> + Send + 'static) -> FfiResult<T> {
+                let handle = GlobalRt::get()?.spawn(fut);
+                handle.await.map_err(|e| KisrError::new(KisrErrorCode::Internal, format!("task failed: {}", e)))?.map_err(KisrFfiError::from)
+        }
+
+        #[derive(uniffi::Object)]
+        pub struct KisrRpcClient {
+                inner: Arc<ClientInner>,
+        }
+
+        #[uniffi::export]
+        impl KisrRpcClient {
+                // `url` None uses the public resolver; the node must be synced and on `network`
+                #[uniffi::constructor]
+                pub async fn connect(network: String, url: Option<String>, json_encoding: bool) -> FfiResult<Arc<Self>> {
+                        let network_id = kisr::network(&network)?;
+                        let encoding = if json_encoding { Encoding::SerdeJson } else { Encoding::Borsh };
+                        let inner = on_runtime(async move {
+                                crate::rpc_ffi::connect::connect(network_id, url.filter(|u| !u.trim().is_empty()), encoding).await
+                        }).await?;
+                        Ok(Arc::new(KisrRpcClient { inner: Arc::new(inner) }))
+                }
+
+                pub async fn disconnect(&self) -> FfiResult<()> {
+                        let inner = self.inner.clone();
+                        on_runtime(async move {
+                                let _ = inner.client.rpc_api().unregister_listener(inner.listener_id).await;
+                                inner.notification_receiver.close();
+                                inner.client.disconnect().await.map_err(|e| KisrError::new(KisrErrorCode::Rpc, format!("disconnect error: {}", e)))
+                        }).await
+                }
+
+                pub async fn create_invite(&self, network: String, private_key_hex: String, amount_sompi: u64, memo: Option<String>, listener: Option<Arc<dyn KisrProgressListener>>) -> FfiResult<CreatedInvite> {
+                        let network_id = kisr::network(&network)?;
+                        let inner = self.inner.clone();
+                        on_runtime(async move {
+                                Invite::create(&inner.client, network_id, &private_key_hex, amount_sompi, memo, &report(&listener)).await
+                        }).await
+                }
+
+                pub async fn get_utxos(&self, addresses: Vec<String>) -> FfiResult<Vec<KisrUtxo>> {
+                        let addresses = addresses.iter()
+                                .map(|a| Address::try_from(a.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, "invalid address").with("address", a.as_str())))
+                                .collect::<Result<Vec<_>, _>>()?;
+                        let inner = self.inner.clone();
+                        on_runtime(async move {
+                                let entries = inner.client.get_utxos_by_addresses(addresses).await
+                                        .map_err(|e| KisrError::new(KisrErrorCode::Rpc, format!("get_utxos_by_addresses error: {}", e)))?;
+                                Ok(entries.iter().map(|e| KisrUtxo::from(to_gen_utxo(e))).collect())
+                        }).await
+                }
+
+                // Returns the txid; outpoints it spends are no longer reserved
+                pub async fn submit_transaction(&self, tx: SafeJsonTx) -> FfiResult<String> {
+                        let inner = self.inner.clone();
+                        on_runtime(async move {
+                                submit_safe(&inner.client, &tx).await
+                        }).await
+                }
+
+                // Returns the redemption txid
+                pub async fn redeem_invite(&self, request: RedeemRequest, listener: Option<Arc<dyn KisrProgressListener>>) -> FfiResult<String> {
+                        let inner = self.inner.clone();
+                        on_runtime(async move {
+                                Invite::redeem(&inner.client, request, &report(&listener)).await
+                        }).await
+                }
+        }
+}
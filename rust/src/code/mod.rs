@@ -0,0 +1,118 @@
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::error::{ffi_guard, ffi_status, ffi_string, KisrError, KisrErrorCode};
+use crate::tx::generator::c_str_arg;
+
+// KISR code format (spec/protocol.md §8): "KISR-" followed by 8 characters from the alphabet below
+pub const CODE_PREFIX: &str = "KISR-";
+pub const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+pub const CODE_BODY_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodeError {
+        Empty,
+        WrongLength { found: usize },
+        // O/0 and I/1 are all excluded from the alphabet, so there is no safe character to map them to
+        AmbiguousCharacter { position: usize, found: char },
+        InvalidCharacter { position: usize, found: char },
+}
+
+impl CodeError {
+        pub fn reason(&self) -> &'static str {
+                match self {
+                        CodeError::Empty => "empty",
+                        CodeError::WrongLength { .. } => "wrongLength",
+                        CodeError::AmbiguousCharacter { .. } => "ambiguousCharacter",
+                        CodeError::InvalidCharacter { .. } => "invalidCharacter",
+                }
+        }
+}
+
+// Reported as `invalidCode`; `context.reason` says why, with the 1-based position or the body length found
+impl From<CodeError> for KisrError {
+        fn from(e: CodeError) -> Self {
+                let err = KisrError::new(KisrErrorCode::InvalidCode, e.to_string()).with("reason", e.reason());
+                match e {
+                        CodeError::Empty => err,
+                        CodeError::WrongLength { found } => err.with("found", found).with("expected", CODE_BODY_LEN),
+                        CodeError::AmbiguousCharacter { position, found } | CodeError::InvalidCharacter { position, found } => err.with("position", position + 1).with("character", found.to_string()),
+                }
+        }
+}
+
+impl fmt::Display for CodeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                        CodeError::Empty => write!(f, "code is empty"),
+                        CodeError::WrongLength { found } => write!(f, "code must have {} characters after the prefix, found {}", CODE_BODY_LEN, found),
+                        CodeError::AmbiguousCharacter { position, found } => write!(f, "character '{}' at position {} is never used in KISR codes (O, 0, I and 1 are excluded)", found, position + 1),
+                        CodeError::InvalidCharacter { position, found } => write!(f, "character '{}' at position {} is not in the KISR alphabet", found, position + 1),
+                }
+        }
+}
+
+fn is_separator(c: char) -> bool {
+        c.is_whitespace() || matches!(c, '-' | '_' | ':' | '.' | '\u{2010}'..='\u{2015}' | '\u{2212}')
+}
+
+/// Generate a canonical `KISR-XXXXXXXX` code from the OS CSPRNG. The alphabet has 32 symbols so `byte % 32` is unbiased.
+pub fn generate() -> String {
+        let mut bytes = [0u8; CODE_BODY_LEN];
+        OsRng.fill_bytes(&mut bytes);
+        let mut out = String::with_capacity(CODE_PREFIX.len() + CODE_BODY_LEN);
+        out.push_str(CODE_PREFIX);
+        for b in bytes.iter() { out.push(CODE_ALPHABET[(*b as usize) % CODE_ALPHABET.len()] as char); }
+        out
+}
+
+/// Normalize user input to the canonical form. Accepts lowercase, a missing or partial `KISR` prefix,
+/// and whitespace or dashes anywhere in the input.
+pub fn normalize(input: &str) -> Result<String, CodeError> {
+        let compact: String = input.chars().filter(|c| !is_separator(*c)).flat_map(|c| c.to_uppercase()).collect();
+        if compact.is_empty() { return Err(CodeError::Empty); }
+        // 'I' is not in the alphabet, so a leading "KISR" is always the prefix and never part of the body
+        let body: &str = compact.strip_prefix("KISR").unwrap_or(&compact);
+        let found = body.chars().count();
+        if found != CODE_BODY_LEN { return Err(CodeError::WrongLength { found }); }
+        for (position, c) in body.chars().enumerate() {
+                if matches!(c, 'O' | '0' | 'I' | '1') { return Err(CodeError::AmbiguousCharacter { position, found: c }); }
+                if !c.is_ascii() || !CODE_ALPHABET.contains(&(c as u8)) { return Err(CodeError::InvalidCharacter { position, found: c }); }
+        }
+        Ok(format!("{}{}", CODE_PREFIX, body))
+}
+
+pub fn validate(input: &str) -> Result<(), CodeError> {
+        normalize(input).map(|_| ())
+}
+
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_code_generate() -> *mut c_char {
+        ffi_guard("kaspa_kisr_code_generate", || {
+                CString::new(generate()).ok().map(CString::into_raw).unwrap_or(ptr::null_mut())
+        })
+}
+
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_code_normalize(input: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_kisr_code_normalize", || {
+                let result = c_str_arg(input, "input").and_then(|s| normalize(&s).map_err(KisrError::from));
+                ffi_string("kaspa_kisr_code_normalize", result)
+        })
+}
+
+// Returns 0 when valid, otherwise a negative KisrErrorCode (invalidCode, with the reason in the last error's context)
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_code_validate(input: *const c_char) -> c_int {
+        ffi_guard("kaspa_kisr_code_validate", || {
+                ffi_status("kaspa_kisr_code_validate", c_str_arg(input, "input").and_then(|s| validate(&s).map_err(KisrError::from)))
+        })
+}
+
+#[cfg(test)]
+mod tests;
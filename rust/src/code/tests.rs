@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+
+use super::*;
+
+#[test]
+fn generated_codes_are_canonical() {
+        let codes: HashSet<String> = (0..256).map(|_| generate()).collect();
+        // 40 bits per code: a repeat here means the RNG is broken
+        assert_eq!(codes.len(), 256);
+        for code in codes.iter() {
+                assert_eq!(code.len(), CODE_PREFIX.len() + CODE_BODY_LEN);
+                assert!(code.starts_with(CODE_PREFIX));
+                assert!(code[CODE_PREFIX.len()..].bytes().all(|b| CODE_ALPHABET.contains(&b)));
+                assert_eq!(normalize(code).as_ref(), Ok(code));
+        }
+}
+
+#[test]
+fn normalize_accepts_the_spec_example_in_any_form() {
+        for input in ["KISR-HNVFFKC8", "kisr-hnvffkc8", "HNVFFKC8", "hnvf-fkc8", " kisr hnvf fkc8 ", "KISR_HNVF.FKC8", "KISRHNVFFKC8", "kisr:HNVFFKC8", "KISR\u{2013}HNVFFKC8"] {
+                assert_eq!(normalize(input).as_deref(), Ok("KISR-HNVFFKC8"), "input {:?}", input);
+        }
+}
+
+#[test]
+fn normalize_reports_why_a_code_is_rejected() {
+        assert_eq!(normalize(""), Err(CodeError::Empty));
+        assert_eq!(normalize(" - "), Err(CodeError::Empty));
+        assert_eq!(normalize("KISR-HNVFFKC"), Err(CodeError::WrongLength { found: 7 }));
+        assert_eq!(normalize("KISR-HNVFFKC88"), Err(CodeError::WrongLength { found: 9 }));
+        assert_eq!(normalize("KISRHNVF"), Err(CodeError::WrongLength { found: 4 }));
+        assert_eq!(normalize("KISR"), Err(CodeError::WrongLength { found: 0 }));
+        assert_eq!(normalize("KISR-HNVFFKC0"), Err(CodeError::AmbiguousCharacter { position: 7, found: '0' }));
+        assert_eq!(normalize("kisr-onvffkc8"), Err(CodeError::AmbiguousCharacter { position: 0, found: 'O' }));
+        assert_eq!(normalize("KISR-HNVFFKC!"), Err(CodeError::InvalidCharacter { position: 7, found: '!' }));
+        assert_eq!(normalize("KISR-HNVFFKCÜ"), Err(CodeError::InvalidCharacter { position: 7, found: 'Ü' }));
+}
+
+#[test]
+fn every_alphabet_symbol_is_accepted() {
+        for chunk in CODE_ALPHABET.chunks(CODE_BODY_LEN) {
+                let body = std::str::from_utf8(chunk).unwrap();
+                assert_eq!(normalize(&body.to_lowercase()), Ok(format!("{}{}", CODE_PREFIX, body)));
+        }
+}
+
+#[test]
+fn validate_and_error_codes_agree_with_normalize() {
+        assert_eq!(validate("kisr-hnvffkc8"), Ok(()));
+        let errors = [CodeError::Empty, CodeError::WrongLength { found: 0 }, CodeError::AmbiguousCharacter { position: 0, found: 'O' }, CodeError::InvalidCharacter { position: 0, found: '!' }];
+        let coded: Vec<KisrError> = errors.iter().map(|&e| e.into()).collect();
+        assert!(coded.iter().all(|e| e.code == KisrErrorCode::InvalidCode));
+        assert_eq!(coded.iter().map(|e| e.context["reason"].as_str().unwrap()).collect::<Vec<_>>(), vec!["empty", "wrongLength", "ambiguousCharacter", "invalidCharacter"]);
+        assert_eq!(coded[3].context["position"], 1);
+        assert_eq!(CodeError::AmbiguousCharacter { position: 2, found: '1' }.to_string(), "character '1' at position 3 is never used in KISR codes (O, 0, I and 1 are excluded)");
+}
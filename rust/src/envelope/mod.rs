@@ -0,0 +1,135 @@
+use std::os::raw::c_char;
+
+use argon2::{Algorithm, Argon2, Params as Argon2Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::error::{ffi_guard, ffi_string, KisrError, KisrErrorCode};
+use crate::tx::generator::c_str_arg;
+
+// Envelope layout (spec/protocol.md §2): "KISR-" | version | salt | nonce | ciphertext, AAD = version || salt
+pub const ENVELOPE_PREFIX: &[u8; 5] = b"KISR-";
+pub const ENVELOPE_VERSION: u8 = 0x01;
+pub const SALT_LEN: usize = 16;
+pub const NONCE_LEN: usize = 24;
+pub const TAG_LEN: usize = 16;
+pub const KEY_LEN: usize = 32;
+
+// Argon2id parameters matching libsodium crypto_pwhash(ops=2, mem=64 MiB, ARGON2ID13)
+const KDF_OPS_LIMIT: u32 = 2;
+const KDF_MEM_LIMIT_KIB: u32 = 64 * 1024;
+const KDF_PARALLELISM: u32 = 1;
+
+fn derive_key(code: &str, salt: &[u8]) -> Result<Zeroizing<[u8; KEY_LEN]>, KisrError> {
+        let params = Argon2Params::new(KDF_MEM_LIMIT_KIB, KDF_OPS_LIMIT, KDF_PARALLELISM, Some(KEY_LEN)).map_err(|e| KisrError::new(KisrErrorCode::Internal, format!("argon2 params: {}", e)))?;
+        let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        argon.hash_password_into(code.as_bytes(), salt, key.as_mut()).map_err(|e| KisrError::new(KisrErrorCode::Internal, format!("argon2: {}", e)))?;
+        Ok(key)
+}
+
+fn cipher_for(key: &[u8; KEY_LEN]) -> Result<XChaCha20Poly1305, KisrError> {
+        XChaCha20Poly1305::new_from_slice(key).map_err(|_| KisrError::new(KisrErrorCode::Internal, "invalid key length"))
+}
+
+/// Encrypt `plaintext` (the TLV buffer) with a key derived from `code`, using fresh OS randomness for salt and nonce.
+pub(crate) fn encrypt(code: &str, plaintext: &[u8]) -> Result<Vec<u8>, KisrError> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        encrypt_with(code, plaintext, &salt, &nonce)
+}
+
+pub(crate) fn encrypt_with(code: &str, plaintext: &[u8], salt: &[u8; SALT_LEN], nonce: &[u8; NONCE_LEN]) -> Result<Vec<u8>, KisrError> {
+        let key = derive_key(code, salt)?;
+        let cipher = cipher_for(&key)?;
+        let mut aad = Vec::with_capacity(1 + SALT_LEN);
+        aad.push(ENVELOPE_VERSION);
+        aad.extend_from_slice(salt);
+        let ciphertext = cipher.encrypt(XNonce::from_slice(nonce), Payload { msg: plaintext, aad: &aad }).map_err(|_| KisrError::new(KisrErrorCode::Internal, "encryption failed"))?;
+        let mut out = Vec::with_capacity(ENVELOPE_PREFIX.len() + aad.len() + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(ENVELOPE_PREFIX);
+        out.extend_from_slice(&aad);
+        out.extend_from_slice(nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+}
+
+/// Validate prefix and version, derive the key from `code` and return the decrypted TLV buffer.
+pub(crate) fn decrypt(code: &str, envelope: &[u8]) -> Result<Vec<u8>, KisrError> {
+        if envelope.len() <= ENVELOPE_PREFIX.len() || &envelope[..ENVELOPE_PREFIX.len()] != ENVELOPE_PREFIX {
+                return Err(KisrError::new(KisrErrorCode::InvalidEnvelope, "invalid envelope prefix"));
+        }
+        let buf = &envelope[ENVELOPE_PREFIX.len()..];
+        if buf.len() < 1 + SALT_LEN + NONCE_LEN + TAG_LEN { return Err(KisrError::new(KisrErrorCode::InvalidEnvelope, "envelope too short").with("length", envelope.len())); }
+        let version = buf[0];
+        if version != ENVELOPE_VERSION { return Err(KisrError::new(KisrErrorCode::InvalidEnvelope, format!("unsupported envelope version {}", version)).with("version", version)); }
+        let salt = &buf[1..1 + SALT_LEN];
+        let nonce = &buf[1 + SALT_LEN..1 + SALT_LEN + NONCE_LEN];
+        let ciphertext = &buf[1 + SALT_LEN + NONCE_LEN..];
+        let key = derive_key(code, salt)?;
+        let cipher = cipher_for(&key)?;
+        let aad = &buf[..1 + SALT_LEN];
+        cipher.decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+                .map_err(|_| KisrError::new(KisrErrorCode::DecryptionFailed, "decryption failed (wrong code or tampered envelope)"))
+}
+
+// A sealed invite envelope as it travels in the anchor payload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Envelope(Vec<u8>);
+
+impl Envelope {
+        pub fn seal(code: &str, plaintext: &[u8]) -> Result<Self, KisrError> {
+                encrypt(code, plaintext).map(Envelope)
+        }
+
+        pub fn open(&self, code: &str) -> Result<Zeroizing<Vec<u8>>, KisrError> {
+                decrypt(code, &self.0).map(Zeroizing::new)
+        }
+
+        // Not validated until opened
+        pub fn from_bytes(bytes: Vec<u8>) -> Self { Envelope(bytes) }
+
+        pub fn from_hex(envelope_hex: &str) -> Result<Self, KisrError> {
+                hex::decode(envelope_hex.trim()).map(Envelope).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid envelope hex"))
+        }
+
+        pub fn as_bytes(&self) -> &[u8] { &self.0 }
+        pub fn into_bytes(self) -> Vec<u8> { self.0 }
+        pub fn to_hex(&self) -> String { hex::encode(&self.0) }
+}
+
+fn envelope_encrypt_hex(code: *const c_char, plaintext_hex: *const c_char) -> Result<String, KisrError> {
+        let code_str = c_str_arg(code, "code")?;
+        let pt_hex = Zeroizing::new(c_str_arg(plaintext_hex, "plaintext_hex")?);
+        let plaintext = Zeroizing::new(hex::decode(pt_hex.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid plaintext hex"))?);
+        Ok(Envelope::seal(&code_str, &plaintext)?.to_hex())
+}
+
+fn envelope_decrypt_hex(code: *const c_char, envelope_hex: *const c_char) -> Result<String, KisrError> {
+        let code_str = c_str_arg(code, "code")?;
+        let envelope = Envelope::from_hex(&c_str_arg(envelope_hex, "envelope_hex")?)?;
+        Ok(hex::encode(envelope.open(&code_str)?.as_slice()))
+}
+
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_envelope_encrypt(code: *const c_char, plaintext_hex: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_kisr_envelope_encrypt", || {
+                ffi_string("kaspa_kisr_envelope_encrypt", envelope_encrypt_hex(code, plaintext_hex))
+        })
+}
+
+// Fails with invalidEnvelope for a malformed envelope and decryptionFailed for a wrong code or tampered ciphertext
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_envelope_decrypt(code: *const c_char, envelope_hex: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_kisr_envelope_decrypt", || {
+                ffi_string("kaspa_kisr_envelope_decrypt", envelope_decrypt_hex(code, envelope_hex))
+        })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,207 @@
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use serde::Serialize;
+
+// Stable values returned by the FFI (as negative c_int) and reported as `code` by kaspa_last_error_json.
+// Never renumber; add new codes at the end of their group.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KisrErrorCode {
+        Ok = 0,
+        // Arguments
+        NullArgument = -1,
+        InvalidHandle = -2,
+        InvalidHex = -3,
+        InvalidLength = -4,
+        InvalidAddress = -5,
+        NetworkMismatch = -6,
+        InvalidNetwork = -7,
+        InvalidJson = -8,
+        InvalidArgument = -9,
+        InvalidSighashType = -10,
+        // Building
+        InsufficientFunds = -20,
+        ImmatureCoinbase = -21,
+        MassLimitExceeded = -22,
+        BuildFailed = -23,
+        StaleTransaction = -24,
+        // Signing
+        InvalidKey = -30,
+        NoMatchingKey = -31,
+        InvalidSignature = -32,
+        SignerFailed = -33,
+        // Invites
+        InvalidEnvelope = -50,
+        DecryptionFailed = -51,
+        InvalidPayload = -52,
+        InvalidCode = -53,
+        UtxoNotFound = -54,
+        // Transport and everything else
+        Rpc = -40,
+        Serialization = -41,
+        Internal = -98,
+        Unspecified = -99,
+}
+
+impl KisrErrorCode {
+        pub fn as_c_int(self) -> c_int { self as i32 }
+
+        pub fn name(self) -> &'static str {
+                match self {
+                        KisrErrorCode::Ok => "ok",
+                        KisrErrorCode::NullArgument => "nullArgument",
+                        KisrErrorCode::InvalidHandle => "invalidHandle",
+                        KisrErrorCode::InvalidHex => "invalidHex",
+                        KisrErrorCode::InvalidLength => "invalidLength",
+                        KisrErrorCode::InvalidAddress => "invalidAddress",
+                        KisrErrorCode::NetworkMismatch => "networkMismatch",
+                        KisrErrorCode::InvalidNetwork => "invalidNetwork",
+                        KisrErrorCode::InvalidJson => "invalidJson",
+                        KisrErrorCode::InvalidArgument => "invalidArgument",
+                        KisrErrorCode::InvalidSighashType => "invalidSighashType",
+                        KisrErrorCode::InsufficientFunds => "insufficientFunds",
+                        KisrErrorCode::ImmatureCoinbase => "immatureCoinbase",
+                        KisrErrorCode::MassLimitExceeded => "massLimitExceeded",
+                        KisrErrorCode::BuildFailed => "buildFailed",
+                        KisrErrorCode::StaleTransaction => "staleTransaction",
+                        KisrErrorCode::InvalidKey => "invalidKey",
+                        KisrErrorCode::NoMatchingKey => "noMatchingKey",
+                        KisrErrorCode::InvalidSignature => "invalidSignature",
+                        KisrErrorCode::SignerFailed => "signerFailed",
+                        KisrErrorCode::InvalidEnvelope => "invalidEnvelope",
+                        KisrErrorCode::DecryptionFailed => "decryptionFailed",
+                        KisrErrorCode::InvalidPayload => "invalidPayload",
+                        KisrErrorCode::InvalidCode => "invalidCode",
+                        KisrErrorCode::UtxoNotFound => "utxoNotFound",
+                        KisrErrorCode::Rpc => "rpc",
+                        KisrErrorCode::Serialization => "serialization",
+                        KisrErrorCode::Internal => "internal",
+                        KisrErrorCode::Unspecified => "unspecified",
+                }
+        }
+}
+
+// An error on its way to the FFI boundary; `context` carries machine-readable details (input index, amounts, ...)
+#[derive(Clone, Debug)]
+pub struct KisrError {
+        pub code: KisrErrorCode,
+        pub message: String,
+        pub context: serde_json::Map<String, serde_json::Value>,
+}
+
+impl KisrError {
+        pub fn new<S: Into<String>>(code: KisrErrorCode, message: S) -> Self {
+                KisrError { code, message: message.into(), context: serde_json::Map::new() }
+        }
+
+        pub fn with<V: Into<serde_json::Value>>(mut self, key: &str, value: V) -> Self {
+                self.context.insert(key.to_string(), value.into());
+                self
+        }
+}
+
+impl fmt::Display for KisrError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.message) }
+}
+
+impl std::error::Error for KisrError {}
+
+thread_local! {
+        static LAST_ERROR: RefCell<Option<KisrError>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn store_last_error(err: KisrError) {
+        LAST_ERROR.with(|e| *e.borrow_mut() = Some(err));
+}
+
+pub(crate) fn take_last_error_full() -> Option<KisrError> {
+        LAST_ERROR.with(|e| e.borrow_mut().take())
+}
+
+pub(crate) fn peek_last_error_code() -> KisrErrorCode {
+        LAST_ERROR.with(|e| e.borrow().as_ref().map(|err| err.code).unwrap_or(KisrErrorCode::Ok))
+}
+
+// Records `err` as raised by `function` and hands back its code, for functions returning c_int
+pub(crate) fn fail(function: &str, err: KisrError) -> c_int {
+        let code = err.code.as_c_int();
+        store_last_error(err.with("function", function));
+        code
+}
+
+// Same, for functions returning a string
+pub(crate) fn fail_null(function: &str, err: KisrError) -> *mut c_char {
+        store_last_error(err.with("function", function));
+        ptr::null_mut()
+}
+
+// 0 on success, otherwise the failure recorded against `function`
+pub(crate) fn ffi_status(function: &str, result: Result<(), KisrError>) -> c_int {
+        match result { Ok(()) => 0, Err(e) => fail(function, e) }
+}
+
+// An owned C string on success (free with kaspa_string_free), otherwise null with the failure recorded
+pub(crate) fn ffi_string(function: &str, result: Result<String, KisrError>) -> *mut c_char {
+        match result.and_then(|s| CString::new(s).map_err(|_| KisrError::new(KisrErrorCode::Internal, "result contains a NUL byte"))) {
+                Ok(s) => s.into_raw(),
+                Err(e) => fail_null(function, e),
+        }
+}
+
+// What an exported function returns when its body panicked
+pub(crate) trait FfiReturn { fn on_panic() -> Self; }
+
+impl FfiReturn for c_int { fn on_panic() -> Self { KisrErrorCode::Internal.as_c_int() } }
+impl FfiReturn for *mut c_char { fn on_panic() -> Self { ptr::null_mut() } }
+impl FfiReturn for () { fn on_panic() -> Self {} }
+
+// Unwinding across `extern "C"` aborts the host app, so every exported function runs its body through this:
+// a panic is recorded as `internal` against `function` and the call fails like any other.
+pub(crate) fn ffi_guard<R: FfiReturn>(function: &str, body: impl FnOnce() -> R) -> R {
+        match panic::catch_unwind(AssertUnwindSafe(body)) {
+                Ok(r) => r,
+                Err(payload) => {
+                        let detail = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                                .or_else(|| payload.downcast_ref::<String>().cloned())
+                                .unwrap_or_else(|| "unknown panic".to_string());
+                        store_last_error(KisrError::new(KisrErrorCode::Internal, format!("panic: {}", detail)).with("function", function));
+                        R::on_panic()
+                }
+        }
+}
+
+// A panic while a registry was locked poisons it; the data is still usable, so later calls carry on
+pub(crate) fn lock_unpoisoned<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+        m.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[derive(Serialize)]
+struct ErrorJson<'a> {
+        code: i32,
+        name: &'a str,
+        message: &'a str,
+        context: &'a serde_json::Map<String, serde_json::Value>,
+}
+
+// `{code, name, message, context}` for the last error on this thread, or null if none; clears it like
+// kaspa_last_error_message does
+#[no_mangle]
+pub extern "C" fn kaspa_last_error_json() -> *mut c_char {
+        ffi_guard("kaspa_last_error_json", || {
+                let Some(err) = take_last_error_full() else { return ptr::null_mut(); };
+                let json = ErrorJson { code: err.code.as_c_int(), name: err.code.name(), message: &err.message, context: &err.context };
+                serde_json::to_string(&json).ok().and_then(|s| CString::new(s).ok()).map(CString::into_raw).unwrap_or(ptr::null_mut())
+        })
+}
+
+// Code of the last error on this thread (0 if none) without clearing it
+#[no_mangle]
+pub extern "C" fn kaspa_last_error_code() -> c_int {
+        ffi_guard("kaspa_last_error_code", || peek_last_error_code().as_c_int())
+}
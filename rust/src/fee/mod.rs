@@ -1,12 +1,115 @@
-use std::ffi::{CStr, CString};
+use std::ffi::CStr;
 use std::os::raw::{c_char, c_int};
-use std::ptr;
 
 use serde::Serialize;
 use kaspa_addresses as kaddr;
 use kaspa_txscript::pay_to_address_script;
 
-use crate::{KaspaUtxoEntry, KaspaOutputEntry, set_last_error};
+use kaspa_consensus_core::config::params::Params;
+use kaspa_consensus_core::mass::{ContextualMasses, MassCalculator};
+use kaspa_consensus_core::network::{NetworkId, NetworkType};
+use kaspa_consensus_core::subnets::SubnetworkId;
+use kaspa_consensus_core::tx::{ScriptPublicKey, SignableTransaction, Transaction, TransactionInput, TransactionOutpoint, TransactionOutput, UtxoEntry};
+
+use crate::{KaspaUtxoEntry, KaspaOutputEntry};
+use crate::error::{ffi_guard, ffi_string, KisrError, KisrErrorCode};
+use crate::tx::generator::{c_str_arg, parse_utxo_entry, to_json_string, TxGenOutput, TxGenUtxo};
+use crate::tx::plan::placeholder_sig_script;
+
+// Mempool standardness limit; heavier transactions are rejected by nodes regardless of fee
+pub(crate) const MAX_STANDARD_TX_MASS: u64 = 100_000;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TxMasses {
+        pub(crate) compute: u64,
+        pub(crate) transient: u64,
+        pub(crate) storage: u64,
+}
+
+impl TxMasses {
+        // The mass the node enforces and charges for
+        pub(crate) fn max(&self) -> u64 { self.compute.max(self.transient).max(self.storage) }
+}
+
+pub(crate) fn calc_tx_masses(mc: &MassCalculator, tx: &Transaction, entries: &[UtxoEntry]) -> TxMasses {
+        let non = mc.calc_non_contextual_masses(tx);
+        let ctx = mc.calc_contextual_masses(&SignableTransaction::with_entries(tx.clone(), entries.to_vec()).as_verifiable()).unwrap_or(ContextualMasses::new(0));
+        TxMasses { compute: non.compute_mass, transient: non.transient_mass, storage: ctx.storage_mass }
+}
+
+#[cfg_attr(not(feature = "rpc"), allow(dead_code))]
+pub(crate) fn calc_tx_mass(mc: &MassCalculator, tx: &Transaction, entries: &[UtxoEntry]) -> u64 {
+        calc_tx_masses(mc, tx, entries).max()
+}
+
+// Fee rate is in sompi per 1000 mass units, rounded up
+pub(crate) fn min_fee_for_mass(mass: u64, rate: u64) -> u64 {
+        let fee = ((mass as u128) * (rate as u128)).div_ceil(1000);
+        if fee > u64::MAX as u128 { u64::MAX } else { fee as u64 }
+}
+
+// What kaspa_estimate_fee_from_entries returns: the mass of the change-less transaction with signatures sized
+// for each UTXO's key type, the fee it needs, and what is left over for change (negative when the inputs fall short)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[serde(rename_all = "camelCase")]
+pub struct FeeEstimate {
+        pub mass: u64,
+        pub compute_mass: u64,
+        pub storage_mass: u64,
+        pub min_fee: u64,
+        pub total_input: u64,
+        pub total_output: u64,
+        pub change: i64,
+}
+
+impl FeeEstimate {
+        // `fee_rate` is sompi per 1000 mass units; zero or negative means the default of 1000
+        pub fn compute(network_id: NetworkId, utxos: &[TxGenUtxo], outputs: &[TxGenOutput], fee_rate: i64, payload: &[u8]) -> Result<Self, KisrError> {
+                if utxos.is_empty() || outputs.is_empty() {
+                        return Err(KisrError::new(KisrErrorCode::InvalidArgument, "at least one utxo and one output are required").with("utxoCount", utxos.len()).with("outputCount", outputs.len()));
+                }
+                let prefix = kaddr::Prefix::from(network_id);
+                let mut inputs: Vec<TransactionInput> = Vec::with_capacity(utxos.len());
+                let mut entries: Vec<UtxoEntry> = Vec::with_capacity(utxos.len());
+                let mut total_input: u64 = 0;
+                for u in utxos.iter() {
+                        let script = ScriptPublicKey::new(u.script_version, u.spk_bytes.clone().into());
+                        inputs.push(TransactionInput::new(TransactionOutpoint { transaction_id: u.txid.into(), index: u.index }, placeholder_sig_script(&u.spk_bytes), 0, 1));
+                        entries.push(UtxoEntry::new(u.amount, script, u.block_daa_score, u.is_coinbase));
+                        total_input = total_input.saturating_add(u.amount);
+                }
+                let mut tx_outputs: Vec<TransactionOutput> = Vec::with_capacity(outputs.len());
+                let mut total_output: u64 = 0;
+                for o in outputs.iter() {
+                        let addr = kaddr::Address::try_from(o.address.as_str())
+                                .map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, format!("invalid address {}", o.address)).with("address", o.address.as_str()))?;
+                        if addr.prefix != prefix {
+                                return Err(KisrError::new(KisrErrorCode::NetworkMismatch, format!("address {} is not for network {}", o.address, network_id))
+                                        .with("address", o.address.as_str()).with("network", network_id.to_string()));
+                        }
+                        tx_outputs.push(TransactionOutput { value: o.amount, script_public_key: pay_to_address_script(&addr) });
+                        total_output = total_output.saturating_add(o.amount);
+                }
+                let mut tx = Transaction::new(0, inputs, tx_outputs, 0, SubnetworkId::default(), 0, payload.to_vec());
+                tx.finalize();
+                let params: Params = network_id.into();
+                let masses = calc_tx_masses(&MassCalculator::new_with_consensus_params(&params), &tx, &entries);
+                let mass = masses.max();
+                let rate = if fee_rate <= 0 { 1000 } else { fee_rate as u64 };
+                let min_fee = min_fee_for_mass(mass, rate);
+                let change = (total_input as i128) - (total_output as i128) - (min_fee as i128);
+                Ok(FeeEstimate {
+                        mass,
+                        compute_mass: masses.compute,
+                        storage_mass: masses.storage,
+                        min_fee,
+                        total_input,
+                        total_output,
+                        change: change.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+                })
+        }
+}
 
 #[no_mangle]
 pub extern "C" fn kaspa_estimate_fee_from_entries(
@@ -18,104 +121,50 @@ pub extern "C" fn kaspa_estimate_fee_from_entries(
         fee_rate_sompi_per_kilomass: i64,
         payload_hex: *const c_char,
 ) -> *mut c_char {
-        use kaspa_consensus_core::tx::{Transaction, TransactionInput, TransactionOutpoint, TransactionOutput, ScriptPublicKey, UtxoEntry};
-        use kaspa_consensus_core::subnets::SubnetworkId;
-
-        if utxos_ptr.is_null() || utxos_len <= 0 || outputs_ptr.is_null() || outputs_len <= 0 {
-                set_last_error("kaspa_estimate_fee_from_entries: invalid arguments");
-                return ptr::null_mut();
-        }
-
-        let utxos: &[KaspaUtxoEntry] = unsafe { std::slice::from_raw_parts(utxos_ptr, utxos_len as usize) };
-        let outputs_in: &[KaspaOutputEntry] = unsafe { std::slice::from_raw_parts(outputs_ptr, outputs_len as usize) };
-
-        let mut inputs: Vec<TransactionInput> = Vec::with_capacity(utxos.len());
-        let mut entries: Vec<UtxoEntry> = Vec::with_capacity(utxos.len());
-        let mut total_input: u64 = 0;
-
-        for u in utxos.iter() {
-                if u.txid_be_hex.is_null() || u.script_pub_key_hex.is_null() { set_last_error("kaspa_estimate_fee_from_entries: null utxo fields"); return ptr::null_mut(); }
-                let txid_be_hex = unsafe { CStr::from_ptr(u.txid_be_hex) }.to_string_lossy().to_string();
-                let script_hex = unsafe { CStr::from_ptr(u.script_pub_key_hex) }.to_string_lossy().to_string();
-                let txid_be_bytes = match hex::decode(txid_be_hex.trim()) { Ok(v) => v, Err(_) => { set_last_error("kaspa_estimate_fee_from_entries: invalid utxo txid hex"); return ptr::null_mut() } };
-                if txid_be_bytes.len() != 32 { set_last_error("kaspa_estimate_fee_from_entries: utxo txid len != 32"); return ptr::null_mut(); }
-                let mut txid_arr = [0u8;32]; txid_arr.copy_from_slice(&txid_be_bytes);
-                let spk_bytes = match crate::decode_spk_hex_strip_optional_version_prefix(&script_hex) { Ok(v) => v, Err(_) => { set_last_error("kaspa_estimate_fee_from_entries: invalid utxo script hex"); return ptr::null_mut() } };
-                let script = ScriptPublicKey::new(0, spk_bytes.into());
-
-                let input = TransactionInput::new(
-                        TransactionOutpoint { transaction_id: txid_arr.into(), index: u.index },
-                        vec![],
-                        0,
-                        1,
-                );
-                inputs.push(input);
-                entries.push(UtxoEntry::new(u.amount, script.clone(), 0, false));
-                total_input = total_input.saturating_add(u.amount);
-        }
+        ffi_guard("kaspa_estimate_fee_from_entries", || {
+                let network_id = if network_is_testnet { NetworkId::with_suffix(NetworkType::Testnet, 10) } else { NetworkId::new(NetworkType::Mainnet) };
+                ffi_string("kaspa_estimate_fee_from_entries", estimate_fee_from_entries(utxos_ptr, utxos_len, outputs_ptr, outputs_len, network_id, fee_rate_sompi_per_kilomass, payload_hex))
+        })
+}
 
-        let mut outputs: Vec<TransactionOutput> = Vec::with_capacity(outputs_in.len());
-        let mut total_output: u64 = 0;
-        for o in outputs_in.iter() {
-                if o.address.is_null() { set_last_error("kaspa_estimate_fee_from_entries: null output address"); return ptr::null_mut(); }
-                let addr_str = unsafe { CStr::from_ptr(o.address) }.to_string_lossy().to_string();
-                let addr = match kaddr::Address::try_from(addr_str.as_str()) { Ok(a) => a, Err(_) => { set_last_error("kaspa_estimate_fee_from_entries: invalid output address"); return ptr::null_mut() } };
-                let expected_prefix = if network_is_testnet { kaddr::Prefix::Testnet } else { kaddr::Prefix::Mainnet };
-                if addr.prefix != expected_prefix { set_last_error("kaspa_estimate_fee_from_entries: address prefix mismatch"); return ptr::null_mut(); }
-                let spk = pay_to_address_script(&addr);
-                outputs.push(TransactionOutput { value: o.amount, script_public_key: spk });
-                total_output = total_output.saturating_add(o.amount);
-        }
+// Same as kaspa_estimate_fee_from_entries, with the network named as in kaspa_tx_generator_new_with_network
+#[no_mangle]
+pub extern "C" fn kaspa_estimate_fee_from_entries_with_network(
+        utxos_ptr: *const KaspaUtxoEntry,
+        utxos_len: c_int,
+        outputs_ptr: *const KaspaOutputEntry,
+        outputs_len: c_int,
+        network: *const c_char,
+        fee_rate_sompi_per_kilomass: i64,
+        payload_hex: *const c_char,
+) -> *mut c_char {
+        ffi_guard("kaspa_estimate_fee_from_entries_with_network", || {
+                let result = c_str_arg(network, "network")
+                        .and_then(|net| crate::kisr::network(&net))
+                        .and_then(|network_id| estimate_fee_from_entries(utxos_ptr, utxos_len, outputs_ptr, outputs_len, network_id, fee_rate_sompi_per_kilomass, payload_hex));
+                ffi_string("kaspa_estimate_fee_from_entries_with_network", result)
+        })
+}
 
-        let payload_bytes: Vec<u8> = if payload_hex.is_null() { vec![] } else {
+fn estimate_fee_from_entries(
+        utxos_ptr: *const KaspaUtxoEntry,
+        utxos_len: c_int,
+        outputs_ptr: *const KaspaOutputEntry,
+        outputs_len: c_int,
+        network_id: NetworkId,
+        fee_rate_sompi_per_kilomass: i64,
+        payload_hex: *const c_char,
+) -> Result<String, KisrError> {
+        if utxos_ptr.is_null() || outputs_ptr.is_null() { return Err(KisrError::new(KisrErrorCode::NullArgument, "null utxos or outputs")); }
+        let utxos_in: &[KaspaUtxoEntry] = unsafe { std::slice::from_raw_parts(utxos_ptr, utxos_len.max(0) as usize) };
+        let outputs_in: &[KaspaOutputEntry] = unsafe { std::slice::from_raw_parts(outputs_ptr, outputs_len.max(0) as usize) };
+        let utxos = utxos_in.iter().map(|u| parse_utxo_entry(u)).collect::<Result<Vec<_>, _>>()?;
+        let outputs = outputs_in.iter()
+                .map(|o| Ok(TxGenOutput { address: c_str_arg(o.address, "address")?, amount: o.amount }))
+                .collect::<Result<Vec<_>, KisrError>>()?;
+        let payload: Vec<u8> = if payload_hex.is_null() { vec![] } else {
                 let s = unsafe { CStr::from_ptr(payload_hex) }.to_string_lossy().to_string();
-                let t = s.trim();
-                if t.is_empty() { vec![] } else { match hex::decode(t) { Ok(v) => v, Err(_) => { set_last_error("kaspa_estimate_fee_from_entries: invalid payload hex"); return ptr::null_mut() } } }
-        };
-
-        let mut tx = Transaction::new(
-                0,
-                inputs,
-                outputs,
-                0,
-                SubnetworkId::default(),
-                0,
-                payload_bytes,
-        );
-
-        for inp in tx.inputs.iter_mut() { inp.signature_script = vec![0u8; 66]; }
-        tx.finalize();
-        let mass: u64 = {
-                use kaspa_consensus_core::config::params::Params;
-                use kaspa_consensus_core::network::NetworkType;
-                use kaspa_consensus_core::mass::{MassCalculator};
-                use kaspa_consensus_core::tx::SignableTransaction;
-                let signable = SignableTransaction::with_entries(tx.clone(), entries.clone());
-                let params: Params = NetworkType::Mainnet.into();
-                let mc = MassCalculator::new_with_consensus_params(&params);
-                let non = mc.calc_non_contextual_masses(&signable.tx);
-                let ctx = mc.calc_contextual_masses(&signable.as_verifiable()).unwrap_or(kaspa_consensus_core::mass::ContextualMasses::new(0));
-                ctx.max(non)
-        };
-
-        let default_rate: u64 = 1000;
-        let rate = if fee_rate_sompi_per_kilomass <= 0 { default_rate } else { fee_rate_sompi_per_kilomass as u64 };
-        let min_fee = ((mass as u128) * (rate as u128) + 999) / 1000;
-        let min_fee_u64 = if min_fee > u64::MAX as u128 { u64::MAX } else { min_fee as u64 };
-        let change: i128 = (total_input as i128) - (total_output as i128) - (min_fee_u64 as i128);
-
-        #[derive(Serialize)]
-        #[serde(rename_all = "camelCase")]
-        struct EstOut { mass: u64, min_fee: u64, total_input: u64, total_output: u64, change: i64 }
-        let out = EstOut {
-                mass,
-                min_fee: min_fee_u64,
-                total_input,
-                total_output,
-                change: if change < i64::MIN as i128 { i64::MIN } else if change > i64::MAX as i128 { i64::MAX } else { change as i64 },
+                hex::decode(s.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid payload hex"))?
         };
-        match serde_json::to_string(&out) {
-                Ok(s) => CString::new(s).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()),
-                Err(_) => { set_last_error("kaspa_estimate_fee_from_entries: serialization error"); ptr::null_mut() },
-        }
+        to_json_string(&FeeEstimate::compute(network_id, &utxos, &outputs, fee_rate_sompi_per_kilomass, &payload)?)
 }
@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+
+use kaspa_consensus_core::network::NetworkId;
+use zeroize::Zeroizing;
+
+use crate::tx::generator::{build_and_sign_with, build_unsigned, parse_address_for, TxGenEntry};
+use crate::tx::select::{reserve_selection, select_inputs};
+use crate::tx::sign::{parse_sighash, InputSigner};
+
+pub use crate::{SafeJsonInput, SafeJsonInputUtxo, SafeJsonOutput, SafeJsonTx};
+pub use crate::error::{KisrError, KisrErrorCode};
+pub use crate::envelope::Envelope;
+pub use crate::fee::FeeEstimate;
+pub use crate::tlv::{KisrOutpoint, KisrPayload, UnknownTlv};
+pub use crate::tx::generator::{TxGenOutput as Output, TxGenUtxo as Utxo};
+pub use crate::tx::select::SelectionStrategy;
+pub use crate::tx::sign::{ALGO_ECDSA, ALGO_SCHNORR};
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::invite::CreatedInvite;
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::redeem::{RedeemFailure, RedeemRequest};
+
+// Safe Rust entry points. Every kaspa_* C function is a thin wrapper over these types, so Rust services, tests
+// and the mobile apps all run the same code.
+
+// "mainnet", "testnet-10", "testnet-11", "simnet", "devnet" or any NetworkId string
+pub fn network(name: &str) -> Result<NetworkId, KisrError> {
+        crate::parse_network_id(name.trim())
+                .ok_or_else(|| KisrError::new(KisrErrorCode::InvalidNetwork, format!("invalid network {}", name.trim())).with("network", name.trim()))
+}
+
+// Transaction builder: add UTXOs and outputs, then build unsigned or signed SafeJSON. Setters chain:
+// `gen.change_address(addr)?.fee_rate(1000).add_output(to, amount)?;`
+#[derive(Clone)]
+pub struct TxGenerator {
+        pub(crate) entry: TxGenEntry,
+}
+
+impl TxGenerator {
+        pub fn new(network_id: NetworkId) -> Self {
+                TxGenerator { entry: TxGenEntry::new(network_id) }
+        }
+
+        pub fn for_network(name: &str) -> Result<Self, KisrError> {
+                Ok(TxGenerator::new(network(name)?))
+        }
+
+        pub fn network_id(&self) -> NetworkId { self.entry.network_id }
+
+        // Drops UTXOs, outputs, payload and per-input settings; network, fee rate and strategy are kept
+        pub fn clear(&mut self) -> &mut Self {
+                let entry = &mut self.entry;
+                entry.utxos.clear();
+                entry.candidates.clear();
+                entry.excluded.clear();
+                entry.outputs.clear();
+                entry.change_address = None;
+                entry.payload.clear();
+                entry.input_sighash.clear();
+                entry.sign_indices = None;
+                self
+        }
+
+        pub fn change_address(&mut self, address: &str) -> Result<&mut Self, KisrError> {
+                parse_address_for(&self.entry, address)?;
+                self.entry.change_address = Some(address.to_string());
+                Ok(self)
+        }
+
+        // Sompi per 1000 mass units; zero or negative means the default of 1000
+        pub fn fee_rate(&mut self, sompi_per_kilomass: i64) -> &mut Self {
+                self.entry.fee_rate = sompi_per_kilomass;
+                self
+        }
+
+        pub fn payload(&mut self, payload: Vec<u8>) -> &mut Self {
+                self.entry.payload = payload;
+                self
+        }
+
+        // Always spent, in the order added
+        pub fn add_utxo(&mut self, utxo: Utxo) -> &mut Self {
+                self.entry.utxos.push(utxo);
+                self
+        }
+
+        // Spent only if the selection strategy picks it; adding the same outpoint twice is a no-op
+        pub fn add_candidate_utxo(&mut self, utxo: Utxo) -> &mut Self {
+                if !self.entry.candidates.iter().any(|c| c.txid == utxo.txid && c.index == utxo.index) { self.entry.candidates.push(utxo); }
+                self
+        }
+
+        pub fn add_output(&mut self, address: &str, amount: u64) -> Result<&mut Self, KisrError> {
+                parse_address_for(&self.entry, address)?;
+                self.entry.outputs.push(Output { address: address.to_string(), amount });
+                Ok(self)
+        }
+
+        // Coinbase UTXOs are only spent once this score is past their maturity
+        pub fn virtual_daa_score(&mut self, virtual_daa_score: u64) -> &mut Self {
+                self.entry.virtual_daa_score = Some(virtual_daa_score);
+                self
+        }
+
+        pub fn selection_strategy(&mut self, strategy: SelectionStrategy) -> &mut Self {
+                self.entry.strategy = strategy;
+                self
+        }
+
+        pub fn exclude_outpoint(&mut self, txid: [u8; 32], index: u32) -> &mut Self {
+                self.entry.excluded.insert((txid, index));
+                self
+        }
+
+        // Input indices follow the build order: UTXOs added with add_utxo first, then the selected candidates
+        pub fn input_sighash(&mut self, input_index: u32, sighash_type: u8) -> Result<&mut Self, KisrError> {
+                parse_sighash(sighash_type)?;
+                self.entry.input_sighash.insert(input_index, sighash_type);
+                Ok(self)
+        }
+
+        // Restricts signing to these input indices; None signs every input again
+        pub fn sign_indices(&mut self, indices: Option<HashSet<u32>>) -> &mut Self {
+                self.entry.sign_indices = indices;
+                self
+        }
+
+        // The UTXOs the next build will spend
+        pub fn selected_utxos(&self) -> Result<Vec<Utxo>, KisrError> {
+                select_inputs(&self.entry)
+        }
+
+        // Reserves the selection process-wide and pins it, so later builds spend exactly these outpoints
+        pub fn reserve_selection(&mut self) -> Result<Vec<Utxo>, KisrError> {
+                let picked = reserve_selection(&self.entry)?;
+                self.entry.held.extend(picked.iter().map(|u| (u.txid, u.index)));
+                self.entry.utxos = picked.clone();
+                self.entry.candidates.clear();
+                self.entry.strategy = SelectionStrategy::None;
+                Ok(picked)
+        }
+
+        pub fn build_unsigned(&self) -> Result<SafeJsonTx, KisrError> {
+                build_unsigned(&self.entry)
+        }
+
+        // `algo` is ALGO_SCHNORR or ALGO_ECDSA; `sighash_type` applies to inputs without an input_sighash override
+        pub fn build_and_sign(&self, private_key_hex: &str, sighash_type: u8, algo: u8) -> Result<SafeJsonTx, KisrError> {
+                let signer = InputSigner::single(private_key_hex, algo)?;
+                build_and_sign_with(&self.entry, &signer, sighash_type)
+        }
+
+        // Each input is signed by the key whose P2PK script (Schnorr or ECDSA) matches its UTXO
+        pub fn build_and_sign_with_keys(&self, private_keys_hex: &[String], sighash_type: u8) -> Result<SafeJsonTx, KisrError> {
+                let signer = InputSigner::matched(private_keys_hex)?;
+                build_and_sign_with(&self.entry, &signer, sighash_type)
+        }
+}
+
+// An invite as the inviter shares it: the code and the TLV payload it unlocks
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct Invite {
+        pub code: String,
+        pub payload: KisrPayload,
+}
+
+impl Invite {
+        // Pairs the payload with a freshly generated code
+        pub fn generate(payload: KisrPayload) -> Self {
+                Invite { code: crate::code::generate(), payload }
+        }
+
+        // Encodes the TLV and encrypts it under the code
+        pub fn seal(&self) -> Result<Envelope, KisrError> {
+                let tlv = Zeroizing::new(self.payload.encode().map_err(|e| KisrError::new(KisrErrorCode::InvalidPayload, e))?);
+                Envelope::seal(&self.code, &tlv)
+        }
+
+        // `code` is accepted in any form kaspa_kisr_code_normalize takes
+        pub fn open(code: &str, envelope: &Envelope) -> Result<Self, KisrError> {
+                let code = crate::code::normalize(code).map_err(KisrError::from)?;
+                let tlv = envelope.open(&code)?;
+                let payload = KisrPayload::decode(&tlv).map_err(|e| KisrError::new(KisrErrorCode::InvalidPayload, e))?;
+                Ok(Invite { code, payload })
+        }
+
+        // Funds the KISRUTXO, presigns it, encrypts the envelope and anchors it. `progress` receives the
+        // KISR_STAGE_* values of kaspa_kisr_create_invite.
+        #[cfg(feature = "rpc")]
+        pub async fn create<P: Fn(i32, &str)>(client: &kaspa_wrpc_client::client::KaspaRpcClient, network_id: NetworkId, private_key_hex: &str, amount_sompi: u64, memo: Option<String>, progress: &P) -> Result<CreatedInvite, KisrError> {
+                crate::rpc_ffi::invite::create_invite(client, network_id, private_key_hex, amount_sompi, memo, progress).await
+        }
+
+        // Redeems to `request.to_address` and returns the redemption txid; the error's `redeemFailure` context
+        // carries the RedeemFailure value
+        #[cfg(feature = "rpc")]
+        pub async fn redeem<P: Fn(i32, &str)>(client: &kaspa_wrpc_client::client::KaspaRpcClient, request: RedeemRequest, progress: &P) -> Result<String, KisrError> {
+                crate::rpc_ffi::redeem::redeem_invite(client, request, progress).await.map_err(|(reason, msg)| reason.error(msg))
+        }
+}
@@ -1,34 +1,44 @@
-use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int};
+// The C ABI takes raw pointers by design: every entry point null-checks them and documents who owns them
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+// For the rpc_ffi modules, which import the crate root
+#[cfg(feature = "rpc")]
+use std::{ffi::CStr, os::raw::c_int};
 use std::ptr;
-use std::cell::RefCell;
 
 use serde::{Serialize, Deserialize};
+use core::str::FromStr;
+use kaspa_consensus_core::network::{NetworkId, NetworkType};
 
-thread_local! {
-    static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
-}
-
-pub fn set_last_error<S: Into<String>>(msg: S) {
-    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg.into()));
-}
+pub use crate::error::{KisrError, KisrErrorCode, kaspa_last_error_json, kaspa_last_error_code};
+use crate::error::ffi_guard;
 
+// "function: message", the form kaspa_last_error_message has always returned
 pub fn take_last_error() -> Option<String> {
-    LAST_ERROR.with(|e| e.borrow_mut().take())
+    crate::error::take_last_error_full().map(|err| match err.context.get("function").and_then(|f| f.as_str()) {
+        Some(function) => format!("{}: {}", function, err.message),
+        None => err.message,
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_last_error_message() -> *mut c_char {
-    match take_last_error() {
-        Some(s) => CString::new(s).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()),
-        None => ptr::null_mut(),
-    }
+    ffi_guard("kaspa_last_error_message", || {
+        match take_last_error() {
+            Some(s) => CString::new(s).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()),
+            None => ptr::null_mut(),
+        }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_string_free(ptr_str: *mut c_char) {
-    if ptr_str.is_null() { return; }
-    unsafe { drop(CString::from_raw(ptr_str)); }
+    ffi_guard("kaspa_string_free", || {
+        if ptr_str.is_null() { return; }
+        unsafe { drop(CString::from_raw(ptr_str)); }
+    })
 }
 
 #[repr(C)]
@@ -40,6 +50,19 @@ pub struct KaspaUtxoEntry {
     pub script_pub_key_hex: *const c_char,
 }
 
+// KaspaUtxoEntry plus the UTXO metadata the node checks: script version, DAA score and coinbase flag
+#[repr(C)]
+#[derive(Clone)]
+pub struct KaspaUtxoEntryV2 {
+    pub txid_be_hex: *const c_char,
+    pub index: u32,
+    pub amount: u64,
+    pub script_pub_key_hex: *const c_char,
+    pub script_version: u16,
+    pub block_daa_score: u64,
+    pub is_coinbase: bool,
+}
+
 #[repr(C)]
 #[derive(Clone)]
 pub struct KaspaOutputEntry {
@@ -47,79 +70,221 @@ pub struct KaspaOutputEntry {
     pub amount: u64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct SafeJsonInputUtxo {
-    pub(crate) address: Option<String>,
-    pub(crate) amount: String,
-    pub(crate) script_public_key: String,
-    pub(crate) block_daa_score: String,
-    pub(crate) is_coinbase: bool,
+pub struct SafeJsonInputUtxo {
+    pub address: Option<String>,
+    pub amount: String,
+    pub script_public_key: String,
+    pub block_daa_score: String,
+    pub is_coinbase: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct SafeJsonInput {
-    pub(crate) transaction_id: String,
-    pub(crate) index: u32,
-    pub(crate) signature_script: String,
-    pub(crate) sequence: String,
-    pub(crate) sig_op_count: u8,
-    pub(crate) utxo: SafeJsonInputUtxo,
+pub struct SafeJsonInput {
+    pub transaction_id: String,
+    pub index: u32,
+    pub signature_script: String,
+    pub sequence: String,
+    pub sig_op_count: u8,
+    pub utxo: SafeJsonInputUtxo,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct SafeJsonOutput {
-    pub(crate) value: String,
-    pub(crate) script_public_key: String,
+pub struct SafeJsonOutput {
+    pub value: String,
+    pub script_public_key: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
 #[serde(rename_all = "camelCase")]
-pub(crate) struct SafeJsonTx {
-    pub(crate) id: String,
-    pub(crate) inputs: Vec<SafeJsonInput>,
-    pub(crate) outputs: Vec<SafeJsonOutput>,
-    pub(crate) version: u16,
-    pub(crate) lock_time: String,
-    pub(crate) gas: String,
-    pub(crate) subnetwork_id: String,
-    pub(crate) payload: String,
-    pub(crate) mass: String,
-}
-
-// Helper shared with fee & rpc code
-pub(crate) fn decode_spk_hex_strip_optional_version_prefix(hex_str: &str) -> Result<Vec<u8>, ()> {
+pub struct SafeJsonTx {
+    pub id: String,
+    pub inputs: Vec<SafeJsonInput>,
+    pub outputs: Vec<SafeJsonOutput>,
+    pub version: u16,
+    pub lock_time: String,
+    pub gas: String,
+    pub subnetwork_id: String,
+    pub payload: String,
+    pub mass: String,
+    #[serde(default)]
+    pub compute_mass: String,
+    #[serde(default)]
+    pub storage_mass: String,
+}
+
+pub(crate) fn parse_network_id(network: &str) -> Option<NetworkId> {
+    match network {
+        "mainnet" => Some(NetworkId::new(NetworkType::Mainnet)),
+        "testnet" => Some(NetworkId::with_suffix(NetworkType::Testnet, 10)),
+        "testnet-10" => Some(NetworkId::with_suffix(NetworkType::Testnet, 10)),
+        "simnet" => Some(NetworkId::new(NetworkType::Simnet)),
+        "devnet" => Some(NetworkId::new(NetworkType::Devnet)),
+        other => NetworkId::from_str(other).ok(),
+    }
+}
+
+pub(crate) fn keypair_from_hex(private_key_hex: &str) -> Result<secp256k1::Keypair, String> {
+    let sk_bytes = hex::decode(private_key_hex.trim()).map_err(|_| "invalid private key hex".to_string())?;
+    if sk_bytes.len() != 32 { return Err("private key must be 32 bytes".to_string()); }
+    secp256k1::Keypair::from_seckey_slice(&secp256k1::Secp256k1::new(), &sk_bytes).map_err(|_| "invalid private key".to_string())
+}
+
+// P2PK (Schnorr) address for a key, as used for the inviter's self-transfers
+pub(crate) fn schnorr_address(keypair: &secp256k1::Keypair, prefix: kaspa_addresses::Prefix) -> kaspa_addresses::Address {
+    let (xonly, _) = keypair.x_only_public_key();
+    kaspa_addresses::Address::new(prefix, kaspa_addresses::Version::PubKey, &xonly.serialize())
+}
+
+// Minimal push of `data` onto a script (OP_DATA_n / OP_PUSHDATA1 / OP_PUSHDATA2)
+pub(crate) fn script_push_data(script: &mut Vec<u8>, data: &[u8]) {
+    let len = data.len();
+    if len <= 75 {
+        script.push(len as u8);
+    } else if len <= 0xff {
+        script.push(0x4c);
+        script.push(len as u8);
+    } else {
+        script.push(0x4d);
+        script.extend_from_slice(&(len as u16).to_le_bytes());
+    }
+    script.extend_from_slice(data);
+}
+
+// SafeJSON / RPC form: 4 hex digits of big-endian script version, then the script
+pub(crate) fn decode_versioned_spk_hex(hex_str: &str) -> Result<(u16, Vec<u8>), ()> {
     let s = hex_str.trim();
-    let payload = if s.len() >= 4 && s[..4].eq_ignore_ascii_case("0000") { &s[4..] } else { s };
-    hex::decode(payload).map_err(|_| ())
+    if s.len() < 4 { return Err(()); }
+    let version = u16::from_str_radix(&s[..4], 16).map_err(|_| ())?;
+    let script = hex::decode(&s[4..]).map_err(|_| ())?;
+    Ok((version, script))
 }
 
+// Helper shared with fee & rpc code. A leading `00xx` is read as the version prefix: no standard script starts with OP_0
+pub(crate) fn decode_spk_hex_version_prefix(hex_str: &str) -> Result<(Option<u16>, Vec<u8>), ()> {
+    let s = hex_str.trim();
+    if s.len() >= 4 && s.starts_with("00") {
+        let version = u16::from_str_radix(&s[..4], 16).map_err(|_| ())?;
+        return Ok((Some(version), hex::decode(&s[4..]).map_err(|_| ())?));
+    }
+    Ok((None, hex::decode(s).map_err(|_| ())?))
+}
+
+pub mod error;
 pub mod tx;
 pub mod fee;
+pub mod envelope;
+pub mod tlv;
+pub mod code;
+pub mod kisr;
 
 #[cfg(feature = "rpc")]
 pub mod rpc_ffi;
 
+// UniFFI interface (Kotlin/Swift) over the same `kisr` types; the C ABI below is unchanged
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+#[cfg(feature = "uniffi")]
+pub mod bindings;
+
 // Re-export the exact FFI symbols expected by KISRService / kisr.rs
 pub use crate::tx::generator::{
     kaspa_tx_generator_new,
+    kaspa_tx_generator_new_with_network,
     kaspa_tx_generator_free,
     kaspa_tx_generator_clear,
     kaspa_tx_generator_set_change_address,
     kaspa_tx_generator_set_fee_rate,
     kaspa_tx_generator_add_utxo,
+    kaspa_tx_generator_add_utxo_v2,
+    kaspa_tx_generator_add_utxos_json,
+    kaspa_tx_generator_set_virtual_daa_score,
+    kaspa_tx_generator_add_candidate_utxo,
+    kaspa_tx_generator_set_selection_strategy,
+    kaspa_tx_generator_selected_outpoints,
+    kaspa_tx_generator_exclude_outpoint,
+    kaspa_tx_generator_reserve_selection,
     kaspa_tx_generator_add_output,
     kaspa_tx_generator_set_payload_hex,
     kaspa_tx_generator_build_unsigned_safejson,
     kaspa_tx_generator_build_and_sign_safejson_with_type_and_algo,
+    kaspa_tx_generator_build_and_sign_safejson_with_keys,
+    kaspa_tx_generator_set_input_sighash,
+    kaspa_tx_generator_set_sign_indices,
+};
+
+pub use crate::tx::chain::kaspa_tx_generator_build_and_sign_chain_safejson;
+
+pub use crate::tx::external::{
+    kaspa_tx_generator_sighashes,
+    kaspa_tx_generator_build_and_sign_safejson_with_signatures,
+    kaspa_tx_generator_build_and_sign_safejson_with_callback,
+};
+
+pub use crate::tx::pskt::{
+    kaspa_tx_generator_build_pskt,
+    kaspa_pskt_sign,
+    kaspa_pskt_add_signature,
+    kaspa_pskt_combine,
+    kaspa_pskt_finalize,
+    kaspa_pskt_extract_safejson,
 };
 
-pub use crate::fee::kaspa_estimate_fee_from_entries;
+pub use crate::tx::reserve::{
+    kaspa_outpoint_reserve,
+    kaspa_outpoint_release,
+};
+
+pub use crate::tx::cancel::kaspa_kisr_cancel_invite;
+
+pub use crate::fee::{
+    kaspa_estimate_fee_from_entries,
+    kaspa_estimate_fee_from_entries_with_network,
+};
+
+pub use crate::envelope::{
+    kaspa_kisr_envelope_encrypt,
+    kaspa_kisr_envelope_decrypt,
+};
+
+pub use crate::tlv::{
+    kaspa_kisr_tlv_encode,
+    kaspa_kisr_tlv_decode,
+};
+
+pub use crate::code::{
+    kaspa_kisr_code_generate,
+    kaspa_kisr_code_normalize,
+    kaspa_kisr_code_validate,
+};
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::connect::{kaspa_rpc_connect, kaspa_rpc_disconnect};
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::watch::{kaspa_rpc_watch_start, kaspa_rpc_watch_poll, kaspa_rpc_watch_stop};
 
 #[cfg(feature = "rpc")]
 pub use crate::rpc_ffi::utxo::kaspa_rpc_get_utxos;
 
 #[cfg(feature = "rpc")]
 pub use crate::rpc_ffi::tx_submit::kaspa_rpc_submit_safe_json;
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::invite::kaspa_kisr_create_invite;
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::redeem::kaspa_kisr_redeem_invite;
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::status::kaspa_kisr_invite_status;
+
+#[cfg(feature = "rpc")]
+pub use crate::rpc_ffi::cancel::kaspa_kisr_cancel_invite_rpc;
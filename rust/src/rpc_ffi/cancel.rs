@@ -0,0 +1,76 @@
+use super::*;
+use kaspa_rpc_core::model::RpcTransactionId;
+
+use crate::error::ffi_string;
+use kaspa_addresses::{Address, Prefix, Version};
+
+use crate::tx::cancel::{parse_targets, resolve_targets, sweep_utxos, CancelTarget};
+use crate::tx::generator::{c_str_arg, to_json_string, TxGenUtxo};
+use super::invite::{rpc_error, to_gen_utxo};
+use super::remote::resolve_anchor;
+
+// `{anchorTxid, code}` targets become envelopes through the same anchor lookup redeem uses
+async fn resolve_anchor_targets(client: &RpcClient, network_id: &NetworkId, targets: Vec<CancelTarget>) -> Result<Vec<CancelTarget>, KisrError> {
+        let mut resolved = Vec::with_capacity(targets.len());
+        for t in targets {
+                match t {
+                        CancelTarget::AnchorTxid { anchor_txid, code } => {
+                                let anchor_id = RpcTransactionId::from_str(anchor_txid.trim())
+                                        .map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, format!("invalid anchorTxid {}", anchor_txid)).with("anchorTxid", anchor_txid.as_str()))?;
+                                let anchor = resolve_anchor(client, network_id, anchor_id, None).await.map_err(rpc_error)?;
+                                resolved.push(CancelTarget::Anchor { code, envelope_hex: hex::encode(anchor.payload), address: None });
+                        }
+                        other => resolved.push(other),
+                }
+        }
+        Ok(resolved)
+}
+
+async fn cancel_invites_rpc(client: &RpcClient, private_key_hex: &str, targets_json: &str, fee_rate: i64) -> Result<SafeJsonTx, KisrError> {
+        let (targets, _) = parse_targets(targets_json)?;
+        let network_id = client.get_server_info().await.map_err(|e| rpc_error(format!("get_server_info error: {:?}", e)))?.network_id;
+        let virtual_daa_score = client.get_block_dag_info().await.map_err(|e| rpc_error(format!("get_block_dag_info error: {:?}", e)))?.virtual_daa_score;
+        let targets = resolve_anchor_targets(client, &network_id, targets).await?;
+        let utxos = verify_utxos(client, private_key_hex, network_id, resolve_targets(private_key_hex, &targets, network_id)?).await?;
+        sweep_utxos(private_key_hex, utxos, network_id, fee_rate, Some(virtual_daa_score))
+}
+
+// Replaces each KISRUTXO with the node's entry, held by the key's Schnorr or ECDSA address, so the sweep signs over
+// the real amount and script. A stated amount that disagrees with the UTXO set is rejected before signing.
+async fn verify_utxos(client: &RpcClient, private_key_hex: &str, network_id: NetworkId, utxos: Vec<TxGenUtxo>) -> Result<Vec<TxGenUtxo>, KisrError> {
+        let public_key = keypair_from_hex(private_key_hex).map_err(|e| KisrError::new(KisrErrorCode::InvalidKey, e))?.public_key();
+        let prefix = Prefix::from(network_id);
+        let addresses = vec![
+                Address::new(prefix, Version::PubKey, &public_key.x_only_public_key().0.serialize()),
+                Address::new(prefix, Version::PubKeyECDSA, &public_key.serialize()),
+        ];
+        let entries = client.get_utxos_by_addresses(addresses).await.map_err(|e| rpc_error(format!("get_utxos_by_addresses error: {:?}", e)))?;
+        utxos.into_iter().map(|u| {
+                let outpoint = format!("{}:{}", hex::encode(u.txid), u.index);
+                let entry = entries.iter().find(|e| e.outpoint.transaction_id.as_bytes() == u.txid && e.outpoint.index == u.index)
+                        .ok_or_else(|| KisrError::new(KisrErrorCode::UtxoNotFound, format!("KISRUTXO {} is not in the key's UTXO set; it may already be redeemed or cancelled", outpoint))
+                                .with("transactionId", hex::encode(u.txid)).with("index", u.index))?;
+                if entry.utxo_entry.amount != u.amount {
+                        return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("amount {} for {} does not match the UTXO amount {}", u.amount, outpoint, entry.utxo_entry.amount))
+                                .with("transactionId", hex::encode(u.txid)).with("index", u.index).with("amountSompi", u.amount.to_string()).with("utxoAmount", entry.utxo_entry.amount.to_string()));
+                }
+                Ok(to_gen_utxo(entry))
+        }).collect()
+}
+
+fn cancel_invite_rpc_json(handle: i32, private_key_hex: *const c_char, invites_json: *const c_char, fee_rate: i64) -> Result<String, KisrError> {
+        let sk_hex = c_str_arg(private_key_hex, "private_key_hex")?;
+        let json = c_str_arg(invites_json, "invites_json")?;
+        let inner = client_for(handle)?;
+        let safe = GlobalRt::get()?.block_on(cancel_invites_rpc(&inner.client, &sk_hex, &json, fee_rate))?;
+        to_json_string(&safe)
+}
+
+// kaspa_kisr_cancel_invite on the connected node's network, also accepting `{anchorTxid, code}` targets.
+// Returns the signed sweep as SafeJSON; submit it with kaspa_rpc_submit_safe_json.
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_cancel_invite_rpc(handle: i32, private_key_hex: *const c_char, invites_json: *const c_char, fee_rate_sompi_per_kilomass: i64) -> *mut c_char {
+        ffi_guard("kaspa_kisr_cancel_invite_rpc", || {
+                ffi_string("kaspa_kisr_cancel_invite_rpc", cancel_invite_rpc_json(handle, private_key_hex, invites_json, fee_rate_sompi_per_kilomass))
+        })
+}
@@ -0,0 +1,96 @@
+use super::*;
+use std::time::Duration;
+use kaspa_wrpc_client::prelude::{ConnectOptions, ConnectStrategy};
+
+use crate::error::ffi_status;
+use crate::tx::generator::c_str_arg;
+use super::invite::rpc_error;
+
+pub const KASPA_RPC_ENCODING_BORSH: c_int = 0;
+pub const KASPA_RPC_ENCODING_JSON: c_int = 1;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const LISTENER_NAME: &str = "kaspa-kisr-ffi";
+
+pub(crate) async fn connect(network_id: NetworkId, url: Option<String>, encoding: Encoding) -> Result<ClientInner, KisrError> {
+        // Explicit URLs get the network's default port when none is given; otherwise the public resolver picks a node
+        let (url, resolver) = match url {
+                Some(u) => {
+                        let invalid_url = |e: String| KisrError::new(KisrErrorCode::InvalidArgument, format!("invalid url {}: {}", u, e)).with("url", u.as_str());
+                        let parsed = RpcClient::parse_url(u.clone(), encoding, NetworkType::from(network_id)).map_err(|e| invalid_url(e.to_string()))?;
+                        Url::parse(&parsed).map_err(|e| invalid_url(e.to_string()))?;
+                        (Some(parsed), None)
+                }
+                None => (None, Some(Resolver::default())),
+        };
+        let client = RpcClient::new(encoding, url.as_deref(), resolver, Some(network_id), None).map_err(|e| rpc_error(format!("client error: {}", e)))?;
+        let options = ConnectOptions {
+                block_async_connect: true,
+                connect_timeout: Some(CONNECT_TIMEOUT),
+                strategy: ConnectStrategy::Fallback,
+                ..Default::default()
+        };
+        client.connect(Some(options)).await.map_err(|e| rpc_error(format!("connect error: {}", e)))?;
+
+        // KISR.md §12: refuse nodes that are not synced or serve another network
+        let info = match client.get_server_info().await {
+                Ok(info) => info,
+                Err(e) => { let _ = client.disconnect().await; return Err(rpc_error(format!("get_server_info error: {:?}", e))); }
+        };
+        if info.network_id != network_id {
+                let _ = client.disconnect().await;
+                return Err(KisrError::new(KisrErrorCode::NetworkMismatch, format!("node is on {}, expected {}", info.network_id, network_id))
+                        .with("network", network_id.to_string()).with("nodeNetwork", info.network_id.to_string()));
+        }
+        if !info.is_synced {
+                let _ = client.disconnect().await;
+                return Err(KisrError::new(KisrErrorCode::Rpc, "node is not synced").with("isSynced", false));
+        }
+
+        let (sender, notification_receiver) = async_channel::unbounded();
+        let listener_id = client.rpc_api().register_new_listener(ChannelConnection::new(LISTENER_NAME, sender, ChannelType::Persistent));
+        Ok(ClientInner { client, listener_id, notification_receiver })
+}
+
+fn connect_handle(network: *const c_char, url: *const c_char, encoding: c_int) -> Result<i32, KisrError> {
+        let net = c_str_arg(network, "network")?;
+        let network_id = parse_network_id(net.trim()).ok_or_else(|| KisrError::new(KisrErrorCode::InvalidNetwork, "invalid network").with("network", net.trim()))?;
+        let url = if url.is_null() { None } else { Some(unsafe { CStr::from_ptr(url) }.to_string_lossy().trim().to_string()).filter(|s| !s.is_empty()) };
+        let encoding = match encoding {
+                KASPA_RPC_ENCODING_BORSH => Encoding::Borsh,
+                KASPA_RPC_ENCODING_JSON => Encoding::SerdeJson,
+                other => return Err(KisrError::new(KisrErrorCode::InvalidArgument, "invalid encoding").with("encoding", other)),
+        };
+        let inner = GlobalRt::get()?.block_on(connect(network_id, url, encoding))?;
+        Ok(store_client(Arc::new(inner)))
+}
+
+fn disconnect_handle(handle: i32) -> Result<(), KisrError> {
+        if handle < 0 { return Err(invalid_handle(handle)); }
+        let inner = take_client(handle).ok_or_else(|| invalid_handle(handle))?;
+        let idx = handle as usize;
+        let watcher = with_watchers(|m| m.lock().get_mut(idx).and_then(Option::take));
+        if let Some(mut w) = watcher {
+                if let Some(tx) = w.shutdown_tx.take() { let _ = tx.send(()); }
+        }
+        with_forwarders(|m| { if let Some(running) = m.lock().get_mut(idx) { *running = false; } });
+        GlobalRt::get()?.block_on(async move {
+                let _ = inner.client.rpc_api().unregister_listener(inner.listener_id).await;
+                inner.notification_receiver.close();
+                inner.client.disconnect().await.map_err(|e| rpc_error(format!("disconnect error: {}", e)))
+        })
+}
+
+// Returns a client handle (>= 0) or a negative KisrErrorCode. `url` may be null to use the public resolver; `encoding` is KASPA_RPC_ENCODING_*.
+#[no_mangle]
+pub extern "C" fn kaspa_rpc_connect(network: *const c_char, url: *const c_char, encoding: c_int) -> i32 {
+        ffi_guard("kaspa_rpc_connect", || {
+                connect_handle(network, url, encoding).unwrap_or_else(|e| fail("kaspa_rpc_connect", e))
+        })
+}
+
+// Stops the handle's watcher and forwarder, unregisters the listener and closes the connection. Returns 0 or a negative KisrErrorCode.
+#[no_mangle]
+pub extern "C" fn kaspa_rpc_disconnect(handle: i32) -> c_int {
+        ffi_guard("kaspa_rpc_disconnect", || ffi_status("kaspa_rpc_disconnect", disconnect_handle(handle)))
+}
@@ -0,0 +1,236 @@
+use super::*;
+use std::os::raw::c_void;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use kaspa_addresses::{Address, Prefix, Version as AddressVersion};
+use kaspa_consensus_core::tx::ScriptPublicKey;
+use kaspa_rpc_core::model::{RpcTransaction, RpcUtxosByAddressesEntry};
+use kaspa_txscript::extract_script_pub_key_address;
+
+use crate::error::ffi_string;
+use crate::tx::generator::{TxGenEntry, TxGenUtxo, TxGenOutput, build_and_sign_entry, c_str_arg, to_json_string};
+use crate::tx::reserve::{release_spent, Reservation};
+use crate::tx::select::SelectionStrategy;
+use crate::tx::sign::{ALGO_SCHNORR, SIGHASH_ALL};
+use crate::tlv::{check_memo, KisrPayload, KisrOutpoint, network_byte_for, DEFAULT_SIGHASH};
+use super::tx_submit::safe_json_to_transaction;
+
+pub type KisrProgressCallback = Option<extern "C" fn(stage: c_int, detail: *const c_char, user_data: *mut c_void)>;
+
+pub const KISR_STAGE_FUNDING_SUBMITTED: c_int = 1;
+pub const KISR_STAGE_UTXO_CONFIRMED: c_int = 2;
+pub const KISR_STAGE_PRESIGNED: c_int = 3;
+pub const KISR_STAGE_ENCRYPTED: c_int = 4;
+pub const KISR_STAGE_ANCHORED: c_int = 5;
+
+// KISR.md §12: retry UTXO discovery with a small backoff, up to 10s total
+const UTXO_WAIT_ATTEMPTS: u32 = 10;
+const UTXO_WAIT_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_FEE_RATE: u64 = 1000;
+
+pub(super) struct Progress {
+        cb: KisrProgressCallback,
+        user_data: *mut c_void,
+}
+
+impl Progress {
+        pub(super) fn new(cb: KisrProgressCallback, user_data: *mut c_void) -> Self { Progress { cb, user_data } }
+
+        pub(super) fn report(&self, stage: c_int, detail: &str) {
+                if let Some(cb) = self.cb {
+                        let c = CString::new(detail).unwrap_or_default();
+                        cb(stage, c.as_ptr(), self.user_data);
+                }
+        }
+}
+
+pub(crate) fn to_gen_utxo(e: &RpcUtxosByAddressesEntry) -> TxGenUtxo {
+        TxGenUtxo {
+                txid: e.outpoint.transaction_id.as_bytes(),
+                index: e.outpoint.index,
+                amount: e.utxo_entry.amount,
+                spk_bytes: e.utxo_entry.script_public_key.script().to_vec(),
+                script_version: e.utxo_entry.script_public_key.version(),
+                block_daa_score: e.utxo_entry.block_daa_score,
+                is_coinbase: e.utxo_entry.is_coinbase,
+        }
+}
+
+// The generator builds whatever it is given; callers check the result actually covers the minimum relay fee
+pub(super) fn pays_min_fee(tx: &SafeJsonTx, rate: u64) -> bool {
+        let total_in: u128 = tx.inputs.iter().filter_map(|i| i.utxo.amount.parse::<u64>().ok()).map(|v| v as u128).sum();
+        let total_out: u128 = tx.outputs.iter().filter_map(|o| o.value.parse::<u64>().ok()).map(|v| v as u128).sum();
+        let mass: u128 = tx.mass.parse::<u64>().unwrap_or(u64::MAX) as u128;
+        let min_fee = (mass * rate as u128).div_ceil(1000);
+        total_in >= total_out + min_fee
+}
+
+pub(crate) async fn submit_safe(client: &RpcClient, safe: &SafeJsonTx) -> Result<String, KisrError> {
+        let tx = safe_json_to_transaction(safe)?;
+        let tx_id = tx.id().to_string();
+        client.submit_transaction(RpcTransaction::from(&tx), false).await.map_err(|e| rpc_error(format!("submit_transaction error: {:?}", e)))?;
+        release_spent(&tx);
+        Ok(tx_id)
+}
+
+// One UTXO-set lookup for an outpoint that must already exist
+pub(super) async fn find_outpoint(client: &RpcClient, address: &Address, txid_hex: &str, index: u32) -> Result<Option<RpcUtxosByAddressesEntry>, String> {
+        let entries = client.get_utxos_by_addresses(vec![address.clone()]).await.map_err(|e| format!("get_utxos_by_addresses error: {:?}", e))?;
+        Ok(entries.into_iter().find(|e| e.outpoint.index == index && e.outpoint.transaction_id.to_string().eq_ignore_ascii_case(txid_hex)))
+}
+
+pub(super) async fn wait_for_outpoint(client: &RpcClient, address: &Address, txid_hex: &str, index: u32) -> Result<RpcUtxosByAddressesEntry, String> {
+        for attempt in 1..=UTXO_WAIT_ATTEMPTS {
+                if let Some(e) = find_outpoint(client, address, txid_hex, index).await? { return Ok(e); }
+                if attempt < UTXO_WAIT_ATTEMPTS { tokio::time::sleep(UTXO_WAIT_DELAY).await; }
+        }
+        Err(format!("utxo {}:{} not found at {}", txid_hex, index, address))
+}
+
+// Where the KISRUTXO lives: an explicit inviter address, the inviter pubkey TLV, or the anchor's first output (a self-transfer)
+pub(super) fn resolve_inviter_address(explicit: Option<Address>, payload: &KisrPayload, anchor_output: Option<&ScriptPublicKey>, prefix: Prefix) -> Result<Address, String> {
+        if let Some(a) = explicit { return Ok(a); }
+        if let Some(pk) = payload.inviter_pub_key.as_ref().filter(|pk| pk.len() == 33) {
+                return Ok(Address::new(prefix, AddressVersion::PubKey, &pk[1..]));
+        }
+        if let Some(spk) = anchor_output {
+                return extract_script_pub_key_address(spk, prefix).map_err(|_| "anchor output is not a standard address".to_string());
+        }
+        Err("payload has no inviter pubkey; supply the inviter address".to_string())
+}
+
+pub(super) fn unix_now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// What kaspa_kisr_create_invite returns once the envelope is anchored
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedInvite {
+        pub code: String,
+        // The anchor transaction carrying the envelope
+        pub txid: String,
+        pub outpoint: KisrOutpoint,
+        #[serde(with = "crate::tlv::u64_string")]
+        pub amount_sompi: u64,
+}
+
+pub(super) fn rpc_error(e: String) -> KisrError { KisrError::new(KisrErrorCode::Rpc, e) }
+
+pub(crate) async fn create_invite<P: Fn(c_int, &str)>(client: &RpcClient, network_id: NetworkId, private_key_hex: &str, amount_sompi: u64, memo: Option<String>, progress: &P) -> Result<CreatedInvite, KisrError> {
+        let network_byte = network_byte_for(&network_id)
+                .ok_or_else(|| KisrError::new(KisrErrorCode::InvalidNetwork, format!("network {} is not supported by KISR", network_id)).with("network", network_id.to_string()))?;
+        if amount_sompi == 0 { return Err(KisrError::new(KisrErrorCode::InvalidArgument, "amount must be greater than zero")); }
+        if let Some(m) = &memo { check_memo(m).map_err(|e| KisrError::new(KisrErrorCode::InvalidArgument, e).with("argument", "memo"))?; }
+        let keypair = keypair_from_hex(private_key_hex).map_err(|e| KisrError::new(KisrErrorCode::InvalidKey, e))?;
+        let self_address = schnorr_address(&keypair, Prefix::from(network_id));
+        let self_address_str = self_address.to_string();
+        // Coinbase UTXOs only count as funds once the node's score says they have matured
+        let virtual_daa_score = client.get_block_dag_info().await.map_err(|e| rpc_error(format!("get_block_dag_info error: {:?}", e)))?.virtual_daa_score;
+
+        // 1. Dedicated KISRUTXO: a self-transfer whose first output is exactly the invite amount
+        let entries = client.get_utxos_by_addresses(vec![self_address.clone()]).await.map_err(|e| rpc_error(format!("get_utxos_by_addresses error: {:?}", e)))?;
+        if entries.is_empty() { return Err(KisrError::new(KisrErrorCode::InsufficientFunds, format!("no UTXOs found for {}", self_address_str)).with("address", self_address_str.as_str())); }
+        let funding = TxGenEntry {
+                change_address: Some(self_address_str.clone()),
+                outputs: vec![TxGenOutput { address: self_address_str.clone(), amount: amount_sompi }],
+                candidates: entries.iter().map(to_gen_utxo).collect(),
+                strategy: SelectionStrategy::LargestFirst,
+                virtual_daa_score: Some(virtual_daa_score),
+                ..TxGenEntry::new(network_id)
+        };
+        let funding_tx = build_and_sign_entry(&funding, private_key_hex, SIGHASH_ALL, ALGO_SCHNORR)?;
+        if !pays_min_fee(&funding_tx, DEFAULT_FEE_RATE) {
+                return Err(KisrError::new(KisrErrorCode::InsufficientFunds, "insufficient balance for amount + fees").with("amount", amount_sompi));
+        }
+        let funding_txid = submit_safe(client, &funding_tx).await?;
+        progress(KISR_STAGE_FUNDING_SUBMITTED, &funding_txid);
+
+        // 2. Wait for the KISRUTXO (output 0) to appear in the UTXO set
+        let kisr_entry = wait_for_outpoint(client, &self_address, &funding_txid, 0).await
+                .map_err(|e| KisrError::new(KisrErrorCode::UtxoNotFound, e).with("transactionId", funding_txid.as_str()).with("index", 0))?;
+        let kisr_utxo = to_gen_utxo(&kisr_entry);
+        // Keep concurrent flows in this process from spending it while the invite is pending; released again if
+        // the invite is never anchored
+        let reservation = Reservation::hold((kisr_utxo.txid, kisr_utxo.index));
+        progress(KISR_STAGE_UTXO_CONFIRMED, &format!("{}:{}", funding_txid, 0));
+
+        // 3. Presign the KISRUTXO input alone with None | AnyoneCanPay
+        let presign_entry = TxGenEntry { utxos: vec![kisr_utxo.clone()], held: [(kisr_utxo.txid, kisr_utxo.index)].into(), virtual_daa_score: Some(virtual_daa_score), ..TxGenEntry::new(network_id) };
+        let presigned = build_and_sign_entry(&presign_entry, private_key_hex, DEFAULT_SIGHASH, ALGO_SCHNORR)?;
+        let presig = presigned.inputs.first().and_then(|i| hex::decode(&i.signature_script).ok()).filter(|v| !v.is_empty())
+                .ok_or_else(|| KisrError::new(KisrErrorCode::BuildFailed, "presigned input has no signature script"))?;
+        progress(KISR_STAGE_PRESIGNED, "");
+
+        // 4. TLV + envelope
+        let invite = crate::kisr::Invite::generate(KisrPayload {
+                outpoint: KisrOutpoint { transaction_id: funding_txid.clone(), index: 0 },
+                presig,
+                sighash: DEFAULT_SIGHASH,
+                inviter_pub_key: Some(keypair.public_key().serialize().to_vec()),
+                amount: kisr_utxo.amount,
+                network: network_byte,
+                timestamp: unix_now(),
+                memo: memo.filter(|m| !m.is_empty()),
+                unknown: Vec::new(),
+        });
+        let envelope = invite.seal()?;
+        progress(KISR_STAGE_ENCRYPTED, "");
+
+        // 5. Anchor the envelope in a self-transfer that never spends the KISRUTXO (spec/protocol.md §5)
+        let entries = client.get_utxos_by_addresses(vec![self_address.clone()]).await.map_err(|e| rpc_error(format!("get_utxos_by_addresses error: {:?}", e)))?;
+        let candidates: Vec<TxGenUtxo> = entries.iter().map(to_gen_utxo).collect();
+        if candidates.iter().all(|u| u.txid == kisr_utxo.txid && u.index == kisr_utxo.index) {
+                return Err(KisrError::new(KisrErrorCode::InsufficientFunds, "no eligible UTXOs available for anchoring (all excluded)"));
+        }
+        let anchor = TxGenEntry {
+                change_address: Some(self_address_str.clone()),
+                payload: envelope.into_bytes(),
+                candidates,
+                strategy: SelectionStrategy::ClosestSingle,
+                excluded: [(kisr_utxo.txid, kisr_utxo.index)].into_iter().collect(),
+                virtual_daa_score: Some(virtual_daa_score),
+                ..TxGenEntry::new(network_id)
+        };
+        let anchor_tx = build_and_sign_entry(&anchor, private_key_hex, SIGHASH_ALL, ALGO_SCHNORR)?;
+        if !pays_min_fee(&anchor_tx, DEFAULT_FEE_RATE) {
+                return Err(KisrError::new(KisrErrorCode::InsufficientFunds, "insufficient funds to anchor the envelope"));
+        }
+        let anchor_txid = submit_safe(client, &anchor_tx).await?;
+        progress(KISR_STAGE_ANCHORED, &anchor_txid);
+        reservation.keep();
+
+        Ok(CreatedInvite {
+                code: invite.code,
+                txid: anchor_txid,
+                outpoint: KisrOutpoint { transaction_id: funding_txid, index: 0 },
+                amount_sompi: kisr_utxo.amount,
+        })
+}
+
+fn create_invite_json(handle: i32, network: *const c_char, private_key_hex: *const c_char, amount_sompi: u64, memo: *const c_char, progress: &Progress) -> Result<String, KisrError> {
+        let network_id = crate::kisr::network(&c_str_arg(network, "network")?)?;
+        let sk_hex = c_str_arg(private_key_hex, "private_key_hex")?;
+        let memo_opt = if memo.is_null() { None } else { Some(unsafe { CStr::from_ptr(memo) }.to_string_lossy().to_string()) };
+        let inner = client_for(handle)?;
+        let rt = GlobalRt::get()?;
+        let created = rt.block_on(create_invite(&inner.client, network_id, &sk_hex, amount_sompi, memo_opt, &|stage, detail| progress.report(stage, detail)))?;
+        to_json_string(&created)
+}
+
+// Creates the KISRUTXO, presigns it, encrypts the envelope and anchors it. Returns JSON `{code, txid, outpoint, amountSompi}`.
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_create_invite(
+        handle: i32,
+        network: *const c_char,
+        private_key_hex: *const c_char,
+        amount_sompi: u64,
+        memo: *const c_char,
+        progress_cb: KisrProgressCallback,
+        user_data: *mut c_void,
+) -> *mut c_char {
+        ffi_guard("kaspa_kisr_create_invite", || {
+                let progress = Progress::new(progress_cb, user_data);
+                ffi_string("kaspa_kisr_create_invite", create_invite_json(handle, network, private_key_hex, amount_sompi, memo, &progress))
+        })
+}
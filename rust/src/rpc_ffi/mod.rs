@@ -8,19 +8,22 @@ use kaspa_wrpc_client::client::KaspaRpcClient as RpcClient;
 use kaspa_wrpc_client::resolver::Resolver;
 use workflow_rpc::encoding::Encoding;
 use kaspa_consensus_core::network::{NetworkId, NetworkType};
-use kaspa_rpc_core::model::RpcAddress;
+use kaspa_rpc_core::model::{RpcAddress, RpcUtxosByAddressesEntry};
 use url::Url;
 use core::str::FromStr;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
-use kaspa_wrpc_client::prelude::{Scope, VirtualDaaScoreChangedScope, BlockAddedScope, Notification, ListenerId, ChannelConnection, ChannelType};
+use kaspa_wrpc_client::prelude::{Scope, VirtualDaaScoreChangedScope, BlockAddedScope, UtxosChangedScope, Notification, ListenerId, ChannelConnection, ChannelType};
 use async_channel::Receiver as AsyncNotificationReceiver;
+use crate::error::{fail, fail_null};
 
 pub(super) struct GlobalRt;
 impl GlobalRt {
-        pub(super) fn get() -> &'static Runtime {
+        // Fails (rather than panicking) when the host will not give us threads
+        pub(super) fn get() -> Result<&'static Runtime, KisrError> {
                 static RT: OnceCell<Runtime> = OnceCell::new();
-                RT.get_or_init(|| tokio::runtime::Builder::new_multi_thread().enable_all().worker_threads(2).build().expect("tokio rt"))
+                RT.get_or_try_init(|| tokio::runtime::Builder::new_multi_thread().enable_all().worker_threads(2).build())
+                        .map_err(|e| KisrError::new(KisrErrorCode::Internal, format!("failed to start tokio runtime: {}", e)))
         }
 }
 
@@ -66,15 +69,12 @@ pub(super) fn get_client(handle: i32) -> Option<Arc<ClientInner>> {
         })
 }
 
-pub(super) fn parse_network_id(network: &str) -> Option<NetworkId> {
-        match network {
-                "mainnet" => Some(NetworkId::new(NetworkType::Mainnet)),
-                "testnet" => Some(NetworkId::with_suffix(NetworkType::Testnet, 10)),
-                "testnet-10" => Some(NetworkId::with_suffix(NetworkType::Testnet, 10)),
-                "simnet" => Some(NetworkId::new(NetworkType::Simnet)),
-                "devnet" => Some(NetworkId::new(NetworkType::Devnet)),
-                other => NetworkId::from_str(other).ok(),
-        }
+pub(super) fn invalid_handle(handle: i32) -> KisrError {
+        KisrError::new(KisrErrorCode::InvalidHandle, "invalid handle").with("handle", handle)
+}
+
+pub(super) fn client_for(handle: i32) -> Result<Arc<ClientInner>, KisrError> {
+        get_client(handle).ok_or_else(|| invalid_handle(handle))
 }
 
 pub(super) fn with_forwarders<F, R>(f: F) -> R where F: FnOnce(&Mutex<Vec<bool>>) -> R {
@@ -82,6 +82,18 @@ pub(super) fn with_forwarders<F, R>(f: F) -> R where F: FnOnce(&Mutex<Vec<bool>>
         f(cell)
 }
 
+// Flattens UtxosChanged entries to the outpoint shape used elsewhere in the FFI
+fn utxo_change_json(entries: &[RpcUtxosByAddressesEntry]) -> Vec<serde_json::Value> {
+        entries.iter().map(|e| serde_json::json!({
+                "address": e.address.as_ref().map(|a| a.to_string()),
+                "transactionId": e.outpoint.transaction_id.to_string(),
+                "index": e.outpoint.index,
+                "amount": e.utxo_entry.amount.to_string(),
+                "blockDaaScore": e.utxo_entry.block_daa_score.to_string(),
+                "isCoinbase": e.utxo_entry.is_coinbase,
+        })).collect()
+}
+
 pub(super) fn ensure_forwarder_running(handle: i32, inner: Arc<ClientInner>) {
         let already_running = with_forwarders(|m| {
                 let mut v = m.lock();
@@ -92,42 +104,44 @@ pub(super) fn ensure_forwarder_running(handle: i32, inner: Arc<ClientInner>) {
                 running
         });
         if already_running { return; }
-        let _ = GlobalRt::get().spawn(async move {
+        // The runtime already exists: the watcher that called us was started on it
+        let Ok(rt) = GlobalRt::get() else { return; };
+        // Detached: it ends when the client's notification channel closes
+        drop(rt.spawn(async move {
                 let rx = inner.notification_receiver.clone();
-                loop {
-                        match rx.recv().await {
-                                Ok(notification) => {
-                                        let mut maybe_json: Option<String> = None;
-                                        match notification {
-                                                Notification::BlockAdded(n) => {
-                                                        let payload = serde_json::json!({"type": "blockAdded", "data": n});
-                                                        maybe_json = Some(payload.to_string());
-                                                }
-                                                Notification::VirtualDaaScoreChanged(n) => {
-                                                        let payload = serde_json::json!({"type": "virtualDaaScoreChanged", "data": n});
-                                                        maybe_json = Some(payload.to_string());
-                                                }
-                                                _ => {}
-                                        }
-                                        if let Some(s) = maybe_json {
-                                                with_watchers(|m| {
-                                                        let map = m.lock();
-                                                        let idx = handle as usize;
-                                                        if let Some(Some(w)) = map.get(idx) {
-                                                                let _ = w._out_tx.send(s);
-                                                        }
-                                                });
-                                        }
+                while let Ok(notification) = rx.recv().await {
+                        let mut maybe_json: Option<String> = None;
+                        match notification {
+                                Notification::BlockAdded(n) => {
+                                        let payload = serde_json::json!({"type": "blockAdded", "data": n});
+                                        maybe_json = Some(payload.to_string());
+                                }
+                                Notification::VirtualDaaScoreChanged(n) => {
+                                        let payload = serde_json::json!({"type": "virtualDaaScoreChanged", "data": n});
+                                        maybe_json = Some(payload.to_string());
+                                }
+                                Notification::UtxosChanged(n) => {
+                                        let payload = serde_json::json!({"type": "utxosChanged", "data": {"added": utxo_change_json(&n.added), "removed": utxo_change_json(&n.removed)}});
+                                        maybe_json = Some(payload.to_string());
                                 }
-                                Err(_) => { break; }
+                                _ => {}
+                        }
+                        if let Some(s) = maybe_json {
+                                with_watchers(|m| {
+                                        let map = m.lock();
+                                        let idx = handle as usize;
+                                        if let Some(Some(w)) = map.get(idx) {
+                                                let _ = w._out_tx.send(s);
+                                        }
+                                });
                         }
                 }
-        });
+        }));
 }
 
 pub(super) struct Watcher {
         pub(super) shutdown_tx: Option<mpsc::UnboundedSender<()>>,
-        pub(super) out_rx: mpsc::UnboundedReceiver<String>,
+        pub(super) out_rx: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<String>>>,
         pub(super) _out_tx: mpsc::UnboundedSender<String>,
         pub(super) _handle: JoinHandle<()>,
 }
@@ -141,3 +155,10 @@ pub(super) fn with_watchers<F, R>(f: F) -> R where F: FnOnce(&Mutex<Vec<Option<W
 
 pub mod utxo;
 pub mod tx_submit;
+pub mod invite;
+pub mod redeem;
+pub mod status;
+pub mod cancel;
+pub(crate) mod remote;
+pub mod connect;
+pub mod watch;
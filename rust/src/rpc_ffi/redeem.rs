@@ -0,0 +1,190 @@
+use super::*;
+use std::os::raw::c_void;
+use kaspa_addresses::{Address, Prefix};
+use kaspa_consensus_core::config::params::Params;
+use kaspa_consensus_core::mass::MassCalculator;
+use kaspa_consensus_core::subnets::SubnetworkId;
+use kaspa_consensus_core::tx::{Transaction, TransactionInput, TransactionOutpoint, TransactionOutput, UtxoEntry};
+use kaspa_rpc_core::model::{RpcTransaction, RpcTransactionId};
+use kaspa_txscript::pay_to_address_script;
+
+use crate::fee::{calc_tx_mass, min_fee_for_mass};
+use crate::tx::reserve::release_spent;
+use crate::tlv::{KisrPayload, network_byte_for, NETWORK_MAINNET, NETWORK_TESTNET_10};
+use super::invite::{find_outpoint, resolve_inviter_address, KisrProgressCallback, Progress};
+use super::remote::resolve_anchor;
+
+pub const KISR_STAGE_ANCHOR_FETCHED: c_int = 11;
+pub const KISR_STAGE_DECRYPTED: c_int = 12;
+pub const KISR_STAGE_UTXO_VERIFIED: c_int = 13;
+pub const KISR_STAGE_REDEEMED: c_int = 14;
+
+const DEFAULT_FEE_RATE: u64 = 1000;
+const MAX_FEE_ITERATIONS: usize = 4;
+
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedeemFailure {
+        InvalidArguments = 1,
+        InvalidCode = 2,
+        AnchorNotFound = 3,
+        InvalidEnvelope = 4,
+        InvalidPayload = 5,
+        NetworkMismatch = 6,
+        InviterAddressUnknown = 7,
+        UtxoNotFound = 8,
+        AmountMismatch = 9,
+        FeeExceedsAmount = 10,
+        SubmitFailed = 11,
+        Rpc = 12,
+}
+
+impl RedeemFailure {
+        // The KisrErrorCode reported for this failure; the RedeemFailure value goes in the `redeemFailure` context
+        pub fn error(self, message: impl Into<String>) -> KisrError {
+                let code = match self {
+                        RedeemFailure::InvalidArguments | RedeemFailure::AnchorNotFound => KisrErrorCode::InvalidArgument,
+                        RedeemFailure::InvalidCode => KisrErrorCode::InvalidCode,
+                        RedeemFailure::InvalidEnvelope => KisrErrorCode::InvalidEnvelope,
+                        RedeemFailure::InvalidPayload | RedeemFailure::AmountMismatch => KisrErrorCode::InvalidPayload,
+                        RedeemFailure::NetworkMismatch => KisrErrorCode::NetworkMismatch,
+                        RedeemFailure::InviterAddressUnknown => KisrErrorCode::InvalidAddress,
+                        RedeemFailure::UtxoNotFound => KisrErrorCode::UtxoNotFound,
+                        RedeemFailure::FeeExceedsAmount => KisrErrorCode::InsufficientFunds,
+                        RedeemFailure::SubmitFailed | RedeemFailure::Rpc => KisrErrorCode::Rpc,
+                };
+                KisrError::new(code, message).with("redeemFailure", self as i32)
+        }
+}
+
+type RedeemResult<T> = Result<T, (RedeemFailure, String)>;
+
+fn fail<T>(reason: RedeemFailure, msg: impl Into<String>) -> RedeemResult<T> { Err((reason, msg.into())) }
+
+// `anchor_payload_hex` skips the anchor lookup (e.g. when the REST API is unreachable); `inviter_address` is
+// only needed for invites without the inviter pubkey TLV whose anchor outputs cannot be looked up
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct RedeemRequest {
+        pub code: String,
+        pub anchor_txid: String,
+        pub to_address: String,
+        pub anchor_payload_hex: Option<String>,
+        pub inviter_address: Option<String>,
+}
+
+pub(crate) async fn redeem_invite<P: Fn(c_int, &str)>(client: &RpcClient, req: RedeemRequest, progress: &P) -> RedeemResult<String> {
+        let code = match crate::code::normalize(&req.code) { Ok(c) => c, Err(e) => return fail(RedeemFailure::InvalidCode, e.to_string()) };
+        let to_address = match Address::try_from(req.to_address.trim()) { Ok(a) => a, Err(_) => return fail(RedeemFailure::InvalidArguments, "invalid destination address") };
+        let anchor_id = match RpcTransactionId::from_str(req.anchor_txid.trim()) { Ok(v) => v, Err(_) => return fail(RedeemFailure::InvalidArguments, "invalid anchor txid") };
+
+        let supplied_payload = match req.anchor_payload_hex.as_deref().map(|h| hex::decode(h.trim())) {
+                Some(Ok(v)) => Some(v),
+                Some(Err(_)) => return fail(RedeemFailure::InvalidArguments, "invalid anchor payload hex"),
+                None => None,
+        };
+        let node_network = match client.get_server_info().await { Ok(info) => info.network_id, Err(e) => return fail(RedeemFailure::Rpc, format!("get_server_info error: {:?}", e)) };
+
+        // 1. Anchor payload: the mempool, then the accepted transaction
+        let anchor = match resolve_anchor(client, &node_network, anchor_id, supplied_payload).await { Ok(a) => a, Err(e) => return fail(RedeemFailure::AnchorNotFound, e) };
+        let envelope = anchor.payload.clone();
+        progress(KISR_STAGE_ANCHOR_FETCHED, &req.anchor_txid);
+
+        // 2. Decrypt and parse the TLV
+        let tlv = match crate::envelope::decrypt(&code, &envelope) { Ok(v) => zeroize::Zeroizing::new(v), Err(e) => return fail(RedeemFailure::InvalidEnvelope, e.message) };
+        let payload = match KisrPayload::decode(&tlv) { Ok(p) => p, Err(e) => return fail(RedeemFailure::InvalidPayload, e) };
+        progress(KISR_STAGE_DECRYPTED, "");
+
+        // 3. Network must agree between the payload, the destination address and the connected node
+        let expected_prefix = match payload.network { NETWORK_MAINNET => Prefix::Mainnet, NETWORK_TESTNET_10 => Prefix::Testnet, other => return fail(RedeemFailure::InvalidPayload, format!("unsupported network byte {}", other)) };
+        if to_address.prefix != expected_prefix { return fail(RedeemFailure::NetworkMismatch, "destination address network does not match the invite"); }
+        if network_byte_for(&node_network) != Some(payload.network) { return fail(RedeemFailure::NetworkMismatch, format!("connected node is on {}", node_network)); }
+
+        // 4. Locate the KISRUTXO: explicit inviter address, inviter pubkey TLV, or the anchor's first output
+        let explicit_inviter = match req.inviter_address.as_deref().map(|a| Address::try_from(a.trim())) {
+                Some(Ok(a)) => Some(a),
+                Some(Err(_)) => return fail(RedeemFailure::InvalidArguments, "invalid inviter address"),
+                None => None,
+        };
+        let inviter_address = match resolve_inviter_address(explicit_inviter, &payload, anchor.first_output.as_ref(), expected_prefix) {
+                Ok(a) => a,
+                Err(e) => return fail(RedeemFailure::InviterAddressUnknown, e),
+        };
+        let utxo = match find_outpoint(client, &inviter_address, &payload.outpoint.transaction_id, payload.outpoint.index).await {
+                Ok(Some(u)) => u,
+                Ok(None) => return fail(RedeemFailure::UtxoNotFound, format!("KISRUTXO {}:{} is spent or unknown at {}", payload.outpoint.transaction_id, payload.outpoint.index, inviter_address)),
+                Err(e) => return fail(RedeemFailure::Rpc, e),
+        };
+        if utxo.utxo_entry.amount != payload.amount { return fail(RedeemFailure::AmountMismatch, format!("on-chain amount {} != invite amount {}", utxo.utxo_entry.amount, payload.amount)); }
+        progress(KISR_STAGE_UTXO_VERIFIED, &format!("{}:{}", payload.outpoint.transaction_id, payload.outpoint.index));
+
+        // 5. Splice the presignature and size the fee from the real signature script
+        let params: Params = node_network.into();
+        let mc = MassCalculator::new_with_consensus_params(&params);
+        let entries = vec![UtxoEntry::new(utxo.utxo_entry.amount, utxo.utxo_entry.script_public_key.clone(), utxo.utxo_entry.block_daa_score, utxo.utxo_entry.is_coinbase)];
+        let out_spk = pay_to_address_script(&to_address);
+        let build = |value: u64| -> Transaction {
+                let input = TransactionInput::new(TransactionOutpoint { transaction_id: utxo.outpoint.transaction_id, index: utxo.outpoint.index }, payload.presig.clone(), 0, 1);
+                let mut tx = Transaction::new(0, vec![input], vec![TransactionOutput { value, script_public_key: out_spk.clone() }], 0, SubnetworkId::default(), 0, vec![]);
+                tx.finalize();
+                tx
+        };
+        let mut fee: u64 = 0;
+        let mut tx = build(payload.amount);
+        for _ in 0..MAX_FEE_ITERATIONS {
+                let mass = calc_tx_mass(&mc, &tx, &entries);
+                let min_fee = min_fee_for_mass(mass, DEFAULT_FEE_RATE);
+                if min_fee >= payload.amount { return fail(RedeemFailure::FeeExceedsAmount, format!("fee {} >= amount {}", min_fee, payload.amount)); }
+                if min_fee <= fee { break; }
+                fee = min_fee;
+                tx = build(payload.amount - fee);
+        }
+
+        // 6. Broadcast
+        let tx_id = tx.id().to_string();
+        if let Err(e) = client.submit_transaction(RpcTransaction::from(&tx), false).await {
+                return fail(RedeemFailure::SubmitFailed, format!("submit_transaction error: {:?}", e));
+        }
+        release_spent(&tx);
+        progress(KISR_STAGE_REDEEMED, &tx_id);
+        Ok(tx_id)
+}
+
+// Redeems `{code, txid}` to `to_address` and returns the redemption txid.
+// On failure returns null, sets the last error and writes a RedeemFailure value to `out_failure` when non-null.
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_redeem_invite(
+        handle: i32,
+        code: *const c_char,
+        anchor_txid: *const c_char,
+        to_address: *const c_char,
+        anchor_payload_hex: *const c_char,
+        inviter_address: *const c_char,
+        progress_cb: KisrProgressCallback,
+        user_data: *mut c_void,
+        out_failure: *mut c_int,
+) -> *mut c_char {
+        ffi_guard("kaspa_kisr_redeem_invite", || {
+                let report = |reason: RedeemFailure, msg: String| -> *mut c_char {
+                        if !out_failure.is_null() { unsafe { *out_failure = reason as c_int; } }
+                        fail_null("kaspa_kisr_redeem_invite", reason.error(msg))
+                };
+                if !out_failure.is_null() { unsafe { *out_failure = 0; } }
+                if code.is_null() || anchor_txid.is_null() || to_address.is_null() { return report(RedeemFailure::InvalidArguments, "null arguments".to_string()); }
+                let Some(inner) = get_client(handle) else { return report(RedeemFailure::InvalidArguments, "invalid handle".to_string()); };
+                let opt_str = |p: *const c_char| if p.is_null() { None } else { Some(unsafe { CStr::from_ptr(p) }.to_string_lossy().to_string()).filter(|s| !s.trim().is_empty()) };
+                let req = RedeemRequest {
+                        code: unsafe { CStr::from_ptr(code) }.to_string_lossy().to_string(),
+                        anchor_txid: unsafe { CStr::from_ptr(anchor_txid) }.to_string_lossy().to_string(),
+                        to_address: unsafe { CStr::from_ptr(to_address) }.to_string_lossy().to_string(),
+                        anchor_payload_hex: opt_str(anchor_payload_hex),
+                        inviter_address: opt_str(inviter_address),
+                };
+                let progress = Progress::new(progress_cb, user_data);
+                let rt = match GlobalRt::get() { Ok(rt) => rt, Err(e) => return fail_null("kaspa_kisr_redeem_invite", e) };
+                match rt.block_on(redeem_invite(&inner.client, req, &|stage, detail| progress.report(stage, detail))) {
+                        Ok(txid) => CString::new(txid).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()),
+                        Err((reason, msg)) => report(reason, msg),
+                }
+        })
+}
@@ -0,0 +1,116 @@
+use super::*;
+use std::time::Duration;
+use kaspa_addresses::Address;
+use kaspa_consensus_core::tx::ScriptPublicKey;
+use kaspa_rpc_core::model::{RpcTransaction, RpcTransactionId};
+use serde::Deserialize;
+
+use crate::tlv::{network_byte_for, NETWORK_MAINNET, NETWORK_TESTNET_10};
+
+// Node RPC serves transactions by id only while they sit in the mempool. Accepted ones come from the same
+// REST API the SDKs read anchors from (KISRRemote.fetchPayload).
+const MAINNET_API: &str = "https://api.kaspa.org";
+const TESTNET_10_API: &str = "https://api-tn10.kaspa.org";
+const API_TIMEOUT: Duration = Duration::from_secs(8);
+// How far back the inviter's history is searched for an accepted spender
+const HISTORY_PAGE: usize = 50;
+const HISTORY_MAX_PAGES: usize = 4;
+
+#[derive(Deserialize)]
+pub(super) struct RemoteInput {
+        pub(super) previous_outpoint_hash: String,
+        #[serde(with = "crate::tlv::u64_string")]
+        pub(super) previous_outpoint_index: u64,
+        #[serde(default)]
+        pub(super) signature_script: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RemoteOutput {
+        // Script bytes without the version, as hex
+        pub(super) script_public_key: String,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RemoteTx {
+        #[serde(default)]
+        pub(super) transaction_id: Option<String>,
+        #[serde(default)]
+        pub(super) payload: Option<String>,
+        #[serde(default)]
+        pub(super) inputs: Option<Vec<RemoteInput>>,
+        #[serde(default)]
+        pub(super) outputs: Option<Vec<RemoteOutput>>,
+}
+
+impl RemoteTx {
+        pub(super) fn spending_input(&self, txid_hex: &str, index: u32) -> Option<&RemoteInput> {
+                self.inputs.as_ref()?.iter().find(|i| i.previous_outpoint_index == index as u64 && i.previous_outpoint_hash.eq_ignore_ascii_case(txid_hex))
+        }
+}
+
+fn api_base(network_id: &NetworkId) -> Result<&'static str, String> {
+        match network_byte_for(network_id) {
+                Some(NETWORK_MAINNET) => Ok(MAINNET_API),
+                Some(NETWORK_TESTNET_10) => Ok(TESTNET_10_API),
+                _ => Err(format!("no transaction API is known for {}", network_id)),
+        }
+}
+
+pub(super) async fn remote_get<T: serde::de::DeserializeOwned + 'static>(network_id: &NetworkId, path: &str) -> Result<T, String> {
+        let url = format!("{}{}", api_base(network_id)?, path);
+        match tokio::time::timeout(API_TIMEOUT, workflow_http::get_json::<T>(url.as_str())).await {
+                Ok(Ok(v)) => Ok(v),
+                Ok(Err(e)) => Err(format!("{} failed: {}", url, e)),
+                Err(_) => Err(format!("{} timed out", url)),
+        }
+}
+
+pub(super) async fn remote_transaction(network_id: &NetworkId, txid: &str) -> Result<RemoteTx, String> {
+        remote_get(network_id, &format!("/transactions/{}?inputs=false&outputs=true&resolve_previous_outpoints=no", txid)).await
+}
+
+// The accepted transaction spending txid:index, searched in the most recent history of the address holding it
+pub(super) async fn remote_spender(network_id: &NetworkId, address: &Address, txid_hex: &str, index: u32) -> Result<Option<RemoteTx>, String> {
+        for page in 0..HISTORY_MAX_PAGES {
+                let path = format!("/addresses/{}/full-transactions?limit={}&offset={}&resolve_previous_outpoints=no", address, HISTORY_PAGE, page * HISTORY_PAGE);
+                let txs: Vec<RemoteTx> = remote_get(network_id, &path).await?;
+                let count = txs.len();
+                if let Some(tx) = txs.into_iter().find(|tx| tx.spending_input(txid_hex, index).is_some()) { return Ok(Some(tx)); }
+                if count < HISTORY_PAGE { break; }
+        }
+        Ok(None)
+}
+
+pub(super) fn remote_spk(o: &RemoteOutput) -> Option<ScriptPublicKey> {
+        hex::decode(o.script_public_key.trim()).ok().map(|script| ScriptPublicKey::new(0, script.into()))
+}
+
+pub(super) struct Anchor {
+        pub(super) payload: Vec<u8>,
+        // Where a KISRUTXO without the inviter pubkey TLV lives: the anchor is a self-transfer
+        pub(super) first_output: Option<ScriptPublicKey>,
+}
+
+impl From<RpcTransaction> for Anchor {
+        fn from(tx: RpcTransaction) -> Self {
+                Anchor { first_output: tx.outputs.first().map(|o| o.script_public_key.clone()), payload: tx.payload }
+        }
+}
+
+// The mempool first, then the REST API once the anchor has been accepted. A payload supplied by the caller wins.
+pub(super) async fn resolve_anchor(client: &RpcClient, network_id: &NetworkId, anchor_id: RpcTransactionId, supplied_payload: Option<Vec<u8>>) -> Result<Anchor, String> {
+        if let Ok(entry) = client.get_mempool_entry(anchor_id, true, false).await {
+                let mut anchor = Anchor::from(entry.transaction);
+                if let Some(p) = supplied_payload { anchor.payload = p; }
+                return Ok(anchor);
+        }
+        if let Some(payload) = supplied_payload { return Ok(Anchor { payload, first_output: None }); }
+        let tx = remote_transaction(network_id, &anchor_id.to_string()).await
+                .map_err(|e| format!("anchor {} is not in the mempool and the accepted lookup failed: {}", anchor_id, e))?;
+        let payload = match tx.payload.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+                Some(p) => hex::decode(p).map_err(|_| format!("anchor {} payload is not hex", anchor_id))?,
+                None => return Err(format!("anchor {} carries no payload", anchor_id)),
+        };
+        Ok(Anchor { payload, first_output: tx.outputs.as_ref().and_then(|o| o.first()).and_then(remote_spk) })
+}
@@ -0,0 +1,155 @@
+use super::*;
+use kaspa_addresses::{Address, Prefix};
+use kaspa_consensus_core::tx::ScriptPublicKey;
+use kaspa_rpc_core::model::RpcTransactionId;
+use kaspa_txscript::pay_to_address_script;
+use serde::Deserialize;
+
+use crate::error::ffi_string;
+use crate::tlv::{KisrPayload, DEFAULT_SIGHASH, NETWORK_MAINNET, NETWORK_TESTNET_10};
+use crate::tx::generator::{c_str_arg, to_json_string};
+use super::invite::{resolve_inviter_address, rpc_error, unix_now};
+use super::remote::{remote_spender, remote_spk, resolve_anchor};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StatusQuery {
+        #[serde(rename_all = "camelCase")]
+        Anchor { anchor_txid: String, code: String, anchor_payload_hex: Option<String>, inviter_address: Option<String> },
+        #[serde(rename_all = "camelCase")]
+        Outpoint { transaction_id: String, index: u32, address: String, amount_sompi: Option<String> },
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct InviteStatus {
+        status: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        outpoint: Option<crate::tlv::KisrOutpoint>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        address: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        amount_sompi: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_daa_score: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        virtual_daa_score: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        age_daa: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        created_at: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        age_seconds: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        spending_txid: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+}
+
+fn invalid(reason: impl Into<String>) -> InviteStatus {
+        InviteStatus { status: "invalid", reason: Some(reason.into()), ..Default::default() }
+}
+
+// A redemption carries the presignature (or any None|AnyoneCanPay signature) on the KISRUTXO input;
+// a cancellation is a sweep that pays only back to the inviter
+fn classify_spend(outputs: &[ScriptPublicKey], input_sig: &[u8], presig: Option<&[u8]>, inviter_spk: &ScriptPublicKey) -> &'static str {
+        let presig_match = match presig { Some(p) => p == input_sig, None => input_sig.last() == Some(&DEFAULT_SIGHASH) };
+        if presig_match { return "redeemed"; }
+        if !outputs.is_empty() && outputs.iter().all(|spk| spk == inviter_spk) { return "cancelled"; }
+        "spent"
+}
+
+async fn invite_status(inner: &ClientInner, query: StatusQuery) -> Result<InviteStatus, KisrError> {
+        let node_network = inner.client.get_server_info().await.map_err(|e| rpc_error(format!("get_server_info error: {:?}", e)))?.network_id;
+        let (txid_hex, index, address, expected_amount, payload): (String, u32, Address, Option<u64>, Option<KisrPayload>) = match query {
+                StatusQuery::Anchor { anchor_txid, code, anchor_payload_hex, inviter_address } => {
+                        let code = match crate::code::normalize(&code) { Ok(c) => c, Err(e) => return Ok(invalid(e.to_string())) };
+                        let anchor_id = RpcTransactionId::from_str(anchor_txid.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid anchorTxid").with("field", "anchorTxid"))?;
+                        let supplied_payload = match anchor_payload_hex { Some(h) => Some(hex::decode(h.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid anchorPayloadHex").with("field", "anchorPayloadHex"))?), None => None };
+                        let anchor = resolve_anchor(&inner.client, &node_network, anchor_id, supplied_payload).await.map_err(rpc_error)?;
+                        let tlv = match crate::envelope::decrypt(&code, &anchor.payload) { Ok(v) => zeroize::Zeroizing::new(v), Err(e) => return Ok(invalid(e.message)) };
+                        let payload = match KisrPayload::decode(&tlv) { Ok(p) => p, Err(e) => return Ok(invalid(e)) };
+                        let prefix = match payload.network { NETWORK_MAINNET => Prefix::Mainnet, NETWORK_TESTNET_10 => Prefix::Testnet, other => return Ok(invalid(format!("unsupported network byte {}", other))) };
+                        let explicit = match inviter_address { Some(a) => Some(Address::try_from(a.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, "invalid inviterAddress").with("address", a.trim()))?), None => None };
+                        let address = resolve_inviter_address(explicit, &payload, anchor.first_output.as_ref(), prefix).map_err(|e| KisrError::new(KisrErrorCode::InvalidArgument, e))?;
+                        (payload.outpoint.transaction_id.clone(), payload.outpoint.index, address, Some(payload.amount), Some(payload))
+                }
+                StatusQuery::Outpoint { transaction_id, index, address, amount_sompi } => {
+                        let address = Address::try_from(address.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, "invalid address").with("address", address.trim()))?;
+                        let amount = match amount_sompi { Some(s) => Some(s.trim().parse::<u64>().map_err(|_| KisrError::new(KisrErrorCode::InvalidArgument, "invalid amountSompi").with("field", "amountSompi"))?), None => None };
+                        (transaction_id, index, address, amount, None)
+                }
+        };
+        if Prefix::from(node_network) != address.prefix { return Ok(invalid(format!("invite address is not on {}", node_network))); }
+        let virtual_daa_score = inner.client.get_block_dag_info().await.map_err(|e| rpc_error(format!("get_block_dag_info error: {:?}", e)))?.virtual_daa_score;
+        let mut status = InviteStatus {
+                status: "pending",
+                outpoint: Some(crate::tlv::KisrOutpoint { transaction_id: txid_hex.clone(), index }),
+                address: Some(address.to_string()),
+                amount_sompi: expected_amount.map(|a| a.to_string()),
+                virtual_daa_score: Some(virtual_daa_score),
+                created_at: payload.as_ref().map(|p| p.timestamp),
+                age_seconds: payload.as_ref().map(|p| unix_now().saturating_sub(p.timestamp)),
+                ..Default::default()
+        };
+
+        let entries = inner.client.get_utxos_by_addresses(vec![address.clone()]).await.map_err(|e| rpc_error(format!("get_utxos_by_addresses error: {:?}", e)))?;
+        if let Some(e) = entries.iter().find(|e| e.outpoint.index == index && e.outpoint.transaction_id.to_string().eq_ignore_ascii_case(&txid_hex)) {
+                if let Some(expected) = expected_amount {
+                        if expected != e.utxo_entry.amount { return Ok(invalid(format!("on-chain amount {} != invite amount {}", e.utxo_entry.amount, expected))); }
+                }
+                status.amount_sompi = Some(e.utxo_entry.amount.to_string());
+                status.block_daa_score = Some(e.utxo_entry.block_daa_score);
+                status.age_daa = Some(virtual_daa_score.saturating_sub(e.utxo_entry.block_daa_score));
+                return Ok(status);
+        }
+
+        // Not in the UTXO set: look for the spender among the inviter's pending mempool transactions, then in
+        // the accepted history. When neither can say who spent it the status is "unknown", with the reason.
+        let inviter_spk = pay_to_address_script(&address);
+        let presig = payload.as_ref().map(|p| p.presig.as_slice());
+        let mempool = inner.client.get_mempool_entries_by_addresses(vec![address.clone()], true, false).await.map_err(|e| rpc_error(format!("get_mempool_entries_by_addresses error: {:?}", e)))?;
+        for by_addr in mempool.iter() {
+                for entry in by_addr.sending.iter() {
+                        let spending_input = entry.transaction.inputs.iter().find(|i| i.previous_outpoint.index == index && i.previous_outpoint.transaction_id.to_string().eq_ignore_ascii_case(&txid_hex));
+                        if let Some(input) = spending_input {
+                                let outputs: Vec<ScriptPublicKey> = entry.transaction.outputs.iter().map(|o| o.script_public_key.clone()).collect();
+                                status.status = classify_spend(&outputs, &input.signature_script, presig, &inviter_spk);
+                                status.spending_txid = entry.transaction.verbose_data.as_ref().map(|v| v.transaction_id.to_string());
+                                return Ok(status);
+                        }
+                }
+        }
+        match remote_spender(&node_network, &address, &txid_hex, index).await {
+                Ok(Some(tx)) => {
+                        let sig = tx.spending_input(&txid_hex, index).and_then(|i| i.signature_script.as_deref()).and_then(|h| hex::decode(h.trim()).ok()).unwrap_or_default();
+                        // An output that cannot be read keeps the spend from passing as a sweep
+                        let outputs: Vec<ScriptPublicKey> = tx.outputs.iter().flatten().map(remote_spk).collect::<Option<Vec<_>>>().unwrap_or_default();
+                        status.status = classify_spend(&outputs, &sig, presig, &inviter_spk);
+                        status.spending_txid = tx.transaction_id;
+                }
+                Ok(None) => {
+                        status.status = "unknown";
+                        status.reason = Some("KISRUTXO is not in the UTXO set and its spender is neither in the mempool nor in the address's recent accepted history".to_string());
+                }
+                Err(e) => {
+                        status.status = "unknown";
+                        status.reason = Some(format!("KISRUTXO is not in the UTXO set and its accepted spender could not be looked up: {}", e));
+                }
+        }
+        Ok(status)
+}
+
+fn invite_status_json(handle: i32, query_json: *const c_char) -> Result<String, KisrError> {
+        let json = c_str_arg(query_json, "query_json")?;
+        let inner = client_for(handle)?;
+        let query: StatusQuery = serde_json::from_str(&json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid json: {}", e)))?;
+        let status = GlobalRt::get()?.block_on(invite_status(&inner, query))?;
+        to_json_string(&status)
+}
+
+// Reports `{status: pending|redeemed|cancelled|spent|unknown|invalid, ...}` for `{anchorTxid, code}` or `{transactionId, index, address}`.
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_invite_status(handle: i32, query_json: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_kisr_invite_status", || ffi_string("kaspa_kisr_invite_status", invite_status_json(handle, query_json)))
+}
@@ -1,15 +1,19 @@
 use super::*;
 
+use crate::error::ffi_string;
+use crate::tx::generator::{c_str_arg, to_json_string};
+use super::invite::rpc_error;
+
+fn get_utxos_json(handle: i32, address: *const c_char) -> Result<String, KisrError> {
+        let addr_str = c_str_arg(address, "address")?;
+        let inner = client_for(handle)?;
+        let addr = RpcAddress::try_from(addr_str.trim()).map_err(|e| KisrError::new(KisrErrorCode::InvalidAddress, format!("invalid address: {:?}", e)).with("address", addr_str.trim()))?;
+        let utxos = GlobalRt::get()?.block_on(inner.client.get_utxos_by_addresses(vec![addr]))
+                .map_err(|e| rpc_error(format!("get_utxos_by_addresses error: {:?}", e)))?;
+        to_json_string(&utxos)
+}
+
 #[no_mangle]
 pub extern "C" fn kaspa_rpc_get_utxos(handle: i32, address: *const c_char) -> *mut c_char {
-        if address.is_null() { set_last_error("kaspa_rpc_get_utxos: null address"); return ptr::null_mut(); }
-        let Some(inner) = get_client(handle) else { set_last_error("kaspa_rpc_get_utxos: invalid handle"); return ptr::null_mut(); };
-        let addr_str = unsafe { CStr::from_ptr(address) }.to_string_lossy().to_string();
-        let addr = match RpcAddress::try_from(addr_str.as_str()) { Ok(a) => a, Err(e) => { set_last_error(format!("kaspa_rpc_get_utxos: invalid address: {:?}", e)); return ptr::null_mut() } };
-        let rt = GlobalRt::get();
-        let res = rt.block_on(async move {
-                let utxos = inner.client.get_utxos_by_addresses(vec![addr]).await.map_err(|e| format!("get_utxos_by_addresses error: {:?}", e))?;
-                Ok::<String, String>(serde_json::to_string(&utxos).unwrap_or_else(|_| "[]".to_string()))
-        });
-        match res { Ok(s) => CString::new(s).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()), Err(e) => { set_last_error(e); ptr::null_mut() } }
+        ffi_guard("kaspa_rpc_get_utxos", || ffi_string("kaspa_rpc_get_utxos", get_utxos_json(handle, address)))
 }
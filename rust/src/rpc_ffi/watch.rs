@@ -0,0 +1,125 @@
+use super::*;
+use std::time::Duration;
+use serde::Deserialize;
+
+use crate::error::ffi_status;
+use crate::tx::generator::c_str_arg;
+use super::invite::rpc_error;
+
+// Scopes are given as names (`["blockAdded"]`) or objects (`[{"type": "utxosChanged", "addresses": [...]}]`)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScopeSpec {
+        Name(String),
+        Object {
+                #[serde(rename = "type")]
+                kind: String,
+                #[serde(default)]
+                addresses: Vec<String>,
+        },
+}
+
+fn parse_scopes(json: &str) -> Result<Vec<Scope>, KisrError> {
+        let specs: Vec<ScopeSpec> = serde_json::from_str(json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid scopes json: {}", e)))?;
+        if specs.is_empty() { return Err(KisrError::new(KisrErrorCode::InvalidArgument, "no scopes")); }
+        let mut scopes = Vec::with_capacity(specs.len());
+        for spec in specs {
+                let (kind, addresses) = match spec { ScopeSpec::Name(k) => (k, Vec::new()), ScopeSpec::Object { kind, addresses } => (kind, addresses) };
+                let scope = match kind.as_str() {
+                        "blockAdded" => Scope::BlockAdded(BlockAddedScope {}),
+                        "virtualDaaScoreChanged" => Scope::VirtualDaaScoreChanged(VirtualDaaScoreChangedScope {}),
+                        "utxosChanged" => {
+                                // An empty address list would subscribe to every UTXO change on the node
+                                if addresses.is_empty() { return Err(KisrError::new(KisrErrorCode::InvalidArgument, "utxosChanged requires addresses").with("scope", "utxosChanged")); }
+                                let mut parsed = Vec::with_capacity(addresses.len());
+                                for a in addresses.iter() {
+                                        parsed.push(RpcAddress::try_from(a.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, format!("invalid address {}", a)).with("address", a.as_str()))?);
+                                }
+                                Scope::UtxosChanged(UtxosChangedScope::new(parsed))
+                        }
+                        other => return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("unsupported scope {}", other)).with("scope", other)),
+                };
+                scopes.push(scope);
+        }
+        Ok(scopes)
+}
+
+fn watch_start(handle: i32, scopes_json: *const c_char) -> Result<(), KisrError> {
+        let json = c_str_arg(scopes_json, "scopes_json")?;
+        let inner = client_for(handle)?;
+        let scopes = parse_scopes(&json)?;
+        let idx = handle as usize;
+        if with_watchers(|m| matches!(m.lock().get(idx), Some(Some(_)))) { return Err(KisrError::new(KisrErrorCode::InvalidArgument, "watcher already running").with("handle", handle)); }
+
+        let rt = GlobalRt::get()?;
+        rt.block_on(async {
+                for scope in scopes.iter() {
+                        inner.client.start_notify(inner.listener_id, scope.clone()).await.map_err(|e| rpc_error(format!("start_notify error: {:?}", e)))?;
+                }
+                Ok::<(), KisrError>(())
+        })?;
+
+        // The task holds the subscriptions until stop (or disconnect) signals it
+        let (shutdown_tx, mut shutdown_rx) = mpsc::unbounded_channel::<()>();
+        let (out_tx, out_rx) = mpsc::unbounded_channel::<String>();
+        let task_inner = inner.clone();
+        let task = rt.spawn(async move {
+                let _ = shutdown_rx.recv().await;
+                for scope in scopes.into_iter() {
+                        let _ = task_inner.client.stop_notify(task_inner.listener_id, scope).await;
+                }
+        });
+        with_watchers(|m| {
+                let mut v = m.lock();
+                if v.len() <= idx { v.resize_with(idx + 1, || None); }
+                v[idx] = Some(Watcher { shutdown_tx: Some(shutdown_tx), out_rx: Arc::new(tokio::sync::Mutex::new(out_rx)), _out_tx: out_tx, _handle: task });
+        });
+        ensure_forwarder_running(handle, inner);
+        Ok(())
+}
+
+fn no_watcher(handle: i32) -> KisrError {
+        KisrError::new(KisrErrorCode::InvalidHandle, "no watcher for handle").with("handle", handle)
+}
+
+// Subscribes the handle's listener to `scopes_json` and starts buffering events for kaspa_rpc_watch_poll. Returns 0 or a negative KisrErrorCode.
+#[no_mangle]
+pub extern "C" fn kaspa_rpc_watch_start(handle: i32, scopes_json: *const c_char) -> c_int {
+        ffi_guard("kaspa_rpc_watch_start", || ffi_status("kaspa_rpc_watch_start", watch_start(handle, scopes_json)))
+}
+
+// Returns the next event as JSON (`{"type": ..., "data": ...}`), or null. On timeout no error is set;
+// a null with an error set means the watcher is gone.
+#[no_mangle]
+pub extern "C" fn kaspa_rpc_watch_poll(handle: i32, timeout_ms: u32) -> *mut c_char {
+        ffi_guard("kaspa_rpc_watch_poll", || {
+                let idx = handle as usize;
+                let Some(rx) = with_watchers(|m| m.lock().get(idx).and_then(|w| w.as_ref()).map(|w| w.out_rx.clone())) else {
+                        return fail_null("kaspa_rpc_watch_poll", no_watcher(handle));
+                };
+                let rt = match GlobalRt::get() { Ok(rt) => rt, Err(e) => return fail_null("kaspa_rpc_watch_poll", e) };
+                let next = rt.block_on(async move {
+                        let mut rx = rx.lock().await;
+                        tokio::time::timeout(Duration::from_millis(timeout_ms as u64), rx.recv()).await
+                });
+                match next {
+                        Ok(Some(s)) => CString::new(s).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()),
+                        Ok(None) => fail_null("kaspa_rpc_watch_poll", KisrError::new(KisrErrorCode::Rpc, "watcher closed").with("handle", handle)),
+                        Err(_) => ptr::null_mut(),
+                }
+        })
+}
+
+// Unsubscribes and drops any buffered events. Returns 0 or a negative KisrErrorCode.
+#[no_mangle]
+pub extern "C" fn kaspa_rpc_watch_stop(handle: i32) -> c_int {
+        ffi_guard("kaspa_rpc_watch_stop", || {
+                let idx = handle as usize;
+                let Some(mut w) = with_watchers(|m| m.lock().get_mut(idx).and_then(Option::take)) else {
+                        return fail("kaspa_rpc_watch_stop", no_watcher(handle));
+                };
+                if let Some(tx) = w.shutdown_tx.take() { let _ = tx.send(()); }
+                if let Ok(rt) = GlobalRt::get() { let _ = rt.block_on(w._handle); }
+                0
+        })
+}
@@ -0,0 +1,243 @@
+use std::os::raw::c_char;
+
+use serde::{Serialize, Deserialize};
+use kaspa_consensus_core::network::{NetworkId, NetworkType};
+
+use crate::error::{ffi_guard, ffi_string, KisrError, KisrErrorCode};
+use crate::tx::generator::{c_str_arg, to_json_string};
+
+// TLV tags (spec/protocol.md §4). Header is tag (1 byte) || length (u16 big-endian), as written by the JS reference.
+pub const TAG_OUTPOINT: u8 = 0x01;
+pub const TAG_PRESIG: u8 = 0x02;
+pub const TAG_SIGHASH: u8 = 0x03;
+pub const TAG_INVITER_PUBKEY: u8 = 0x04;
+pub const TAG_AMOUNT: u8 = 0x05;
+pub const TAG_NETWORK: u8 = 0x06;
+pub const TAG_TIMESTAMP: u8 = 0x07;
+pub const TAG_MEMO: u8 = 0x08;
+
+pub const DEFAULT_SIGHASH: u8 = 0x82;
+pub const NETWORK_MAINNET: u8 = 0;
+pub const NETWORK_TESTNET_10: u8 = 1;
+pub const MEMO_MAX_CHARS: usize = 40;
+
+const HEADER_LEN: usize = 3;
+
+mod hex_bytes {
+        use serde::{Deserialize, Deserializer, Serializer};
+        pub fn serialize<S: Serializer>(v: &Vec<u8>, s: S) -> Result<S::Ok, S::Error> { s.serialize_str(&hex::encode(v)) }
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+                let s = String::deserialize(d)?;
+                hex::decode(s.trim()).map_err(serde::de::Error::custom)
+        }
+}
+
+mod opt_hex_bytes {
+        use serde::{Deserialize, Deserializer, Serializer};
+        pub fn serialize<S: Serializer>(v: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+                match v { Some(b) => s.serialize_str(&hex::encode(b)), None => s.serialize_none() }
+        }
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+                match Option::<String>::deserialize(d)? {
+                        Some(s) if !s.trim().is_empty() => hex::decode(s.trim()).map(Some).map_err(serde::de::Error::custom),
+                        _ => Ok(None),
+                }
+        }
+}
+
+pub(crate) mod u64_string {
+        use serde::{Deserialize, Deserializer, Serializer};
+        pub fn serialize<S: Serializer>(v: &u64, s: S) -> Result<S::Ok, S::Error> { s.serialize_str(&v.to_string()) }
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+                #[derive(Deserialize)]
+                #[serde(untagged)]
+                enum StrOrNum { S(String), N(u64) }
+                match StrOrNum::deserialize(d)? {
+                        StrOrNum::S(s) => s.trim().parse::<u64>().map_err(serde::de::Error::custom),
+                        StrOrNum::N(n) => Ok(n),
+                }
+        }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[serde(rename_all = "camelCase")]
+pub struct KisrOutpoint {
+        pub transaction_id: String,
+        pub index: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[serde(rename_all = "camelCase")]
+pub struct UnknownTlv {
+        pub tag: u8,
+        #[serde(rename = "valueHex", with = "hex_bytes")]
+        pub value: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+#[serde(rename_all = "camelCase")]
+pub struct KisrPayload {
+        pub outpoint: KisrOutpoint,
+        #[serde(rename = "presigHex", with = "hex_bytes")]
+        pub presig: Vec<u8>,
+        #[serde(default = "default_sighash")]
+        pub sighash: u8,
+        #[serde(rename = "inviterPubKeyHex", default, with = "opt_hex_bytes", skip_serializing_if = "Option::is_none")]
+        pub inviter_pub_key: Option<Vec<u8>>,
+        #[serde(rename = "amountSompi", with = "u64_string")]
+        pub amount: u64,
+        pub network: u8,
+        pub timestamp: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub memo: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub unknown: Vec<UnknownTlv>,
+}
+
+fn default_sighash() -> u8 { DEFAULT_SIGHASH }
+
+// Only mainnet and testnet-10 have a network byte (spec/protocol.md §4)
+pub fn network_byte_for(network_id: &NetworkId) -> Option<u8> {
+        match (network_id.network_type, network_id.suffix) {
+                (NetworkType::Mainnet, _) => Some(NETWORK_MAINNET),
+                (NetworkType::Testnet, Some(10)) => Some(NETWORK_TESTNET_10),
+                _ => None,
+        }
+}
+
+fn write_tlv(out: &mut Vec<u8>, tag: u8, value: &[u8]) -> Result<(), String> {
+        if value.len() > u16::MAX as usize { return Err(format!("tag 0x{:02x}: value too large ({} bytes)", tag, value.len())); }
+        out.push(tag);
+        out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        out.extend_from_slice(value);
+        Ok(())
+}
+
+fn expect_len(tag: u8, v: &[u8], len: usize) -> Result<(), String> {
+        if v.len() != len { Err(format!("tag 0x{:02x}: expected {} bytes, got {}", tag, len, v.len())) } else { Ok(()) }
+}
+
+// Lets callers reject a memo before doing anything that encode would otherwise fail after
+pub fn check_memo(memo: &str) -> Result<(), String> {
+        if memo.chars().count() > MEMO_MAX_CHARS { return Err(format!("memo: must be {} characters or fewer", MEMO_MAX_CHARS)); }
+        Ok(())
+}
+
+impl KisrPayload {
+        pub fn encode(&self) -> Result<Vec<u8>, String> {
+                let txid = hex::decode(self.outpoint.transaction_id.trim()).map_err(|_| "outpoint: invalid txid hex".to_string())?;
+                if txid.len() != 32 { return Err("outpoint: txid must be 32 bytes".to_string()); }
+                if self.presig.is_empty() { return Err("presig: must not be empty".to_string()); }
+                if let Some(pk) = &self.inviter_pub_key { expect_len(TAG_INVITER_PUBKEY, pk, 33)?; }
+                if self.network != NETWORK_MAINNET && self.network != NETWORK_TESTNET_10 { return Err(format!("network: unsupported value {}", self.network)); }
+                if let Some(m) = &self.memo { check_memo(m)?; }
+                let mut out = Vec::with_capacity(128 + self.presig.len());
+                let mut outpoint = Vec::with_capacity(36);
+                outpoint.extend(txid.iter().rev());
+                outpoint.extend_from_slice(&self.outpoint.index.to_le_bytes());
+                write_tlv(&mut out, TAG_OUTPOINT, &outpoint)?;
+                write_tlv(&mut out, TAG_PRESIG, &self.presig)?;
+                write_tlv(&mut out, TAG_SIGHASH, &[self.sighash])?;
+                if let Some(pk) = &self.inviter_pub_key { write_tlv(&mut out, TAG_INVITER_PUBKEY, pk)?; }
+                write_tlv(&mut out, TAG_AMOUNT, &self.amount.to_le_bytes())?;
+                write_tlv(&mut out, TAG_NETWORK, &[self.network])?;
+                write_tlv(&mut out, TAG_TIMESTAMP, &self.timestamp.to_le_bytes())?;
+                if let Some(m) = &self.memo { if !m.is_empty() { write_tlv(&mut out, TAG_MEMO, m.as_bytes())?; } }
+                let mut seen = [false; 256];
+                for u in self.unknown.iter() {
+                        if (TAG_OUTPOINT..=TAG_MEMO).contains(&u.tag) { return Err(format!("unknown: tag 0x{:02x} is a known tag", u.tag)); }
+                        if seen[u.tag as usize] { return Err(format!("unknown: duplicate tag 0x{:02x}", u.tag)); }
+                        seen[u.tag as usize] = true;
+                        write_tlv(&mut out, u.tag, &u.value)?;
+                }
+                Ok(out)
+        }
+
+        pub fn decode(buf: &[u8]) -> Result<Self, String> {
+                let mut fields: [Option<&[u8]>; (TAG_MEMO + 1) as usize] = [None; (TAG_MEMO + 1) as usize];
+                let mut unknown: Vec<UnknownTlv> = Vec::new();
+                let mut o = 0usize;
+                while o < buf.len() {
+                        if buf.len() - o < HEADER_LEN { return Err(format!("truncated header at offset {}", o)); }
+                        let tag = buf[o];
+                        let len = u16::from_be_bytes([buf[o + 1], buf[o + 2]]) as usize;
+                        o += HEADER_LEN;
+                        if buf.len() - o < len { return Err(format!("tag 0x{:02x}: declared length {} exceeds remaining {} bytes", tag, len, buf.len() - o)); }
+                        let v = &buf[o..o + len];
+                        o += len;
+                        if (TAG_OUTPOINT..=TAG_MEMO).contains(&tag) {
+                                if fields[tag as usize].is_some() { return Err(format!("duplicate tag 0x{:02x}", tag)); }
+                                fields[tag as usize] = Some(v);
+                        } else {
+                                if unknown.iter().any(|u| u.tag == tag) { return Err(format!("duplicate tag 0x{:02x}", tag)); }
+                                unknown.push(UnknownTlv { tag, value: v.to_vec() });
+                        }
+                }
+                let required = |tag: u8, name: &str| fields[tag as usize].ok_or_else(|| format!("missing required tag 0x{:02x} ({})", tag, name));
+                let outpoint_raw = required(TAG_OUTPOINT, "outpoint")?;
+                expect_len(TAG_OUTPOINT, outpoint_raw, 36)?;
+                let txid_be: Vec<u8> = outpoint_raw[..32].iter().rev().cloned().collect();
+                let index = u32::from_le_bytes([outpoint_raw[32], outpoint_raw[33], outpoint_raw[34], outpoint_raw[35]]);
+                let presig = required(TAG_PRESIG, "presig")?;
+                if presig.is_empty() { return Err("tag 0x02: presig must not be empty".to_string()); }
+                let sighash = required(TAG_SIGHASH, "sighash")?;
+                expect_len(TAG_SIGHASH, sighash, 1)?;
+                let inviter_pub_key = match fields[TAG_INVITER_PUBKEY as usize] {
+                        Some(v) => { expect_len(TAG_INVITER_PUBKEY, v, 33)?; Some(v.to_vec()) },
+                        None => None,
+                };
+                let amount = required(TAG_AMOUNT, "amount")?;
+                expect_len(TAG_AMOUNT, amount, 8)?;
+                let network = required(TAG_NETWORK, "network")?;
+                expect_len(TAG_NETWORK, network, 1)?;
+                if network[0] != NETWORK_MAINNET && network[0] != NETWORK_TESTNET_10 { return Err(format!("tag 0x06: unsupported network {}", network[0])); }
+                let timestamp = required(TAG_TIMESTAMP, "timestamp")?;
+                expect_len(TAG_TIMESTAMP, timestamp, 8)?;
+                let memo = match fields[TAG_MEMO as usize] {
+                        Some(v) => Some(std::str::from_utf8(v).map_err(|_| "tag 0x08: memo is not valid UTF-8".to_string())?.to_string()),
+                        None => None,
+                };
+                let mut amount_arr = [0u8; 8]; amount_arr.copy_from_slice(amount);
+                let mut ts_arr = [0u8; 8]; ts_arr.copy_from_slice(timestamp);
+                Ok(KisrPayload {
+                        outpoint: KisrOutpoint { transaction_id: hex::encode(txid_be), index },
+                        presig: presig.to_vec(),
+                        sighash: sighash[0],
+                        inviter_pub_key,
+                        amount: u64::from_le_bytes(amount_arr),
+                        network: network[0],
+                        timestamp: u64::from_le_bytes(ts_arr),
+                        memo,
+                        unknown,
+                })
+        }
+}
+
+fn tlv_encode_hex(payload_json: *const c_char) -> Result<String, KisrError> {
+        let json = c_str_arg(payload_json, "payload_json")?;
+        let payload: KisrPayload = serde_json::from_str(&json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid json: {}", e)))?;
+        payload.encode().map(hex::encode).map_err(|e| KisrError::new(KisrErrorCode::InvalidPayload, e))
+}
+
+fn tlv_decode_json(tlv_hex: *const c_char) -> Result<String, KisrError> {
+        let s = c_str_arg(tlv_hex, "tlv_hex")?;
+        let buf = hex::decode(s.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid hex").with("argument", "tlv_hex"))?;
+        let payload = KisrPayload::decode(&buf).map_err(|e| KisrError::new(KisrErrorCode::InvalidPayload, e))?;
+        to_json_string(&payload)
+}
+
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_tlv_encode(payload_json: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_kisr_tlv_encode", || ffi_string("kaspa_kisr_tlv_encode", tlv_encode_hex(payload_json)))
+}
+
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_tlv_decode(tlv_hex: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_kisr_tlv_decode", || ffi_string("kaspa_kisr_tlv_decode", tlv_decode_json(tlv_hex)))
+}
+
+#[cfg(test)]
+mod tests;
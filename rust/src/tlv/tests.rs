@@ -0,0 +1,136 @@
+use super::*;
+
+// Built by hand from spec/protocol.md §4 the way the JS reference writes it: tag, u16 big-endian length, value;
+// outpoint txid little-endian, amount and timestamp u64 little-endian
+const TLV_HEX: &str = "010024bf9f32d429e58a7ad179daf2c6ac0e8d2b2165486fdef3279c4011b2d42d68b10100000002004241000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f820300018205000800e1f505000000000600010007000800f153650000000008000757656c636f6d65";
+const TXID: &str = "b1682dd4b211409c27f3de6f4865212b8d0eacc6f2da79d17a8ae529d4329fbf";
+
+fn vector_payload() -> KisrPayload {
+        let mut presig = vec![0x41];
+        presig.extend(0u8..64);
+        presig.push(0x82);
+        KisrPayload {
+                outpoint: KisrOutpoint { transaction_id: TXID.to_string(), index: 1 },
+                presig,
+                sighash: DEFAULT_SIGHASH,
+                inviter_pub_key: None,
+                amount: 100_000_000,
+                network: NETWORK_MAINNET,
+                timestamp: 1_700_000_000,
+                memo: Some("Welcome".to_string()),
+                unknown: vec![],
+        }
+}
+
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        out.extend_from_slice(value);
+        out
+}
+
+#[test]
+fn encode_matches_reference_vector() {
+        assert_eq!(hex::encode(vector_payload().encode().unwrap()), TLV_HEX);
+}
+
+#[test]
+fn decode_reads_reference_vector() {
+        assert_eq!(KisrPayload::decode(&hex::decode(TLV_HEX).unwrap()).unwrap(), vector_payload());
+}
+
+#[test]
+fn optional_and_unknown_tags_round_trip() {
+        let mut payload = vector_payload();
+        payload.inviter_pub_key = Some([0x02; 33].to_vec());
+        payload.network = NETWORK_TESTNET_10;
+        payload.memo = None;
+        // Asset range from spec/protocol.md §11
+        payload.unknown = vec![UnknownTlv { tag: 0x20, value: vec![20] }, UnknownTlv { tag: 0x21, value: b"token".to_vec() }];
+        let encoded = payload.encode().unwrap();
+        assert!(encoded.ends_with(&[tlv(0x20, &[20]), tlv(0x21, b"token")].concat()));
+        assert_eq!(KisrPayload::decode(&encoded).unwrap(), payload);
+}
+
+#[test]
+fn empty_memo_is_not_written() {
+        let mut payload = vector_payload();
+        payload.memo = Some(String::new());
+        let decoded = KisrPayload::decode(&payload.encode().unwrap()).unwrap();
+        assert_eq!(decoded.memo, None);
+}
+
+#[test]
+fn tags_are_accepted_in_any_order() {
+        let buf = hex::decode(TLV_HEX).unwrap();
+        let first_len = 3 + 36;
+        let reordered = [&buf[first_len..], &buf[..first_len]].concat();
+        assert_eq!(KisrPayload::decode(&reordered).unwrap(), vector_payload());
+}
+
+#[test]
+fn decode_rejects_malformed_buffers() {
+        let buf = hex::decode(TLV_HEX).unwrap();
+        // Header cut short, then a value shorter than its declared length
+        assert!(KisrPayload::decode(&[buf.as_slice(), &[0x09, 0x00]].concat()).unwrap_err().contains("truncated header"));
+        assert!(KisrPayload::decode(&[buf.as_slice(), &[0x09, 0x00, 0x05, 0x01]].concat()).unwrap_err().contains("exceeds remaining"));
+        assert!(KisrPayload::decode(&[buf.as_slice(), &tlv(TAG_MEMO, b"again")].concat()).unwrap_err().contains("duplicate tag 0x08"));
+        assert!(KisrPayload::decode(&[buf.as_slice(), &tlv(0x20, &[1]), &tlv(0x20, &[2])].concat()).unwrap_err().contains("duplicate tag 0x20"));
+        // The outpoint is the first item: 3 + 36 bytes
+        assert!(KisrPayload::decode(&buf[3 + 36..]).unwrap_err().contains("missing required tag 0x01"));
+        let short_outpoint = [tlv(TAG_OUTPOINT, &[0; 35]), buf[3 + 36..].to_vec()].concat();
+        assert!(KisrPayload::decode(&short_outpoint).unwrap_err().contains("expected 36 bytes"));
+}
+
+#[test]
+fn decode_rejects_invalid_values() {
+        let payload = vector_payload();
+        let mut body = payload.encode().unwrap();
+        let network_at = body.windows(4).position(|w| w == [TAG_NETWORK, 0x00, 0x01, NETWORK_MAINNET]).unwrap();
+        body[network_at + 3] = 7;
+        assert!(KisrPayload::decode(&body).unwrap_err().contains("unsupported network 7"));
+        let bad_memo = [hex::decode(TLV_HEX).unwrap()[..TLV_HEX.len() / 2 - 10].to_vec(), tlv(TAG_MEMO, &[0xff, 0xfe])].concat();
+        assert!(KisrPayload::decode(&bad_memo).unwrap_err().contains("not valid UTF-8"));
+}
+
+#[test]
+fn encode_rejects_invalid_payloads() {
+        let with = |f: fn(&mut KisrPayload)| { let mut p = vector_payload(); f(&mut p); p.encode().unwrap_err() };
+        assert!(with(|p| p.outpoint.transaction_id = "abcd".into()).contains("32 bytes"));
+        assert!(with(|p| p.presig.clear()).contains("presig"));
+        assert!(with(|p| p.inviter_pub_key = Some(vec![0x02; 32])).contains("expected 33 bytes"));
+        assert!(with(|p| p.network = 2).contains("unsupported value 2"));
+        assert!(with(|p| p.memo = Some("x".repeat(MEMO_MAX_CHARS + 1))).contains("40 characters"));
+        assert!(with(|p| p.unknown = vec![UnknownTlv { tag: TAG_AMOUNT, value: vec![] }]).contains("known tag"));
+        assert!(with(|p| p.unknown = vec![UnknownTlv { tag: 0x30, value: vec![] }, UnknownTlv { tag: 0x30, value: vec![] }]).contains("duplicate tag 0x30"));
+        // The limit counts characters, not bytes
+        let mut payload = vector_payload();
+        payload.memo = Some("é".repeat(MEMO_MAX_CHARS));
+        assert!(payload.encode().is_ok());
+}
+
+#[test]
+fn json_form_uses_hex_and_string_amounts() {
+        let json = serde_json::to_value(vector_payload()).unwrap();
+        assert_eq!(json["outpoint"]["transactionId"], TXID);
+        assert_eq!(json["amountSompi"], "100000000");
+        assert_eq!(json["presigHex"].as_str().unwrap().len(), 66 * 2);
+        assert!(json.get("inviterPubKeyHex").is_none() && json.get("unknown").is_none());
+        let back: KisrPayload = serde_json::from_value(json).unwrap();
+        assert_eq!(back, vector_payload());
+        // Numeric amounts and a missing sighash are accepted on input
+        let loose = serde_json::json!({
+                "outpoint": { "transactionId": TXID, "index": 1 },
+                "presigHex": "41", "amountSompi": 5, "network": 1, "timestamp": 0, "inviterPubKeyHex": ""
+        });
+        let payload: KisrPayload = serde_json::from_value(loose).unwrap();
+        assert_eq!((payload.amount, payload.sighash, payload.inviter_pub_key), (5, DEFAULT_SIGHASH, None));
+}
+
+#[test]
+fn network_byte_covers_mainnet_and_testnet_10_only() {
+        assert_eq!(network_byte_for(&NetworkId::new(NetworkType::Mainnet)), Some(NETWORK_MAINNET));
+        assert_eq!(network_byte_for(&NetworkId::with_suffix(NetworkType::Testnet, 10)), Some(NETWORK_TESTNET_10));
+        assert_eq!(network_byte_for(&NetworkId::with_suffix(NetworkType::Testnet, 11)), None);
+        assert_eq!(network_byte_for(&NetworkId::new(NetworkType::Devnet)), None);
+}
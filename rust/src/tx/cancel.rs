@@ -0,0 +1,208 @@
+use std::os::raw::c_char;
+
+use serde::Deserialize;
+use kaspa_addresses::{Address, Prefix};
+use kaspa_consensus_core::network::NetworkId;
+use kaspa_txscript::pay_to_address_script;
+use secp256k1::PublicKey;
+
+use crate::{keypair_from_hex, parse_network_id, schnorr_address};
+use crate::envelope::Envelope;
+use crate::error::{ffi_guard, ffi_string, KisrError, KisrErrorCode};
+use crate::kisr::Invite;
+use crate::tlv::network_byte_for;
+use super::generator::{build_and_sign_with, c_str_arg, parse_txid, to_json_string, TxGenEntry, TxGenUtxo};
+use super::sign::{p2pk_scripts, InputSigner, SIGHASH_ALL};
+
+// Each invite is a known outpoint + amount, or a code with the anchor's envelope (or the anchor txid, which
+// kaspa_kisr_cancel_invite_rpc resolves through the node) so the outpoint can be recovered. A KISRUTXO that is
+// itself a coinbase output states `isCoinbase` and `blockDaaScore`. `address` names the inviter address holding
+// the KISRUTXO when it is the key's ECDSA address rather than its Schnorr one.
+// Offline, `amountSompi` must be the UTXO's exact amount: it is signed over, and the node rejects a sweep signed
+// over the wrong amount with a signature error. kaspa_kisr_cancel_invite_rpc checks it against the UTXO set.
+#[derive(Deserialize)]
+#[serde(untagged, rename_all = "camelCase")]
+pub(crate) enum CancelTarget {
+        #[serde(rename_all = "camelCase")]
+        Outpoint {
+                transaction_id: String,
+                index: u32,
+                amount_sompi: serde_json::Value,
+                #[serde(default)]
+                is_coinbase: bool,
+                #[serde(default)]
+                block_daa_score: Option<serde_json::Value>,
+                #[serde(default)]
+                address: Option<String>,
+        },
+        #[serde(rename_all = "camelCase")]
+        Anchor {
+                code: String,
+                envelope_hex: String,
+                #[serde(default)]
+                address: Option<String>,
+        },
+        #[cfg_attr(not(feature = "rpc"), allow(dead_code))]
+        #[serde(rename_all = "camelCase")]
+        AnchorTxid { anchor_txid: String, code: String },
+}
+
+// The invites to sweep: a bare array of targets, or `{invites, virtualDaaScore}` when a target is a coinbase output
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CancelRequest {
+        Targets(Vec<CancelTarget>),
+        #[serde(rename_all = "camelCase")]
+        WithScore { invites: Vec<CancelTarget>, virtual_daa_score: Option<serde_json::Value> },
+}
+
+fn parse_amount(v: &serde_json::Value) -> Option<u64> {
+        match v {
+                serde_json::Value::String(s) => s.trim().parse::<u64>().ok(),
+                serde_json::Value::Number(n) => n.as_u64(),
+                _ => None,
+        }
+}
+
+// The key's Schnorr P2PK script, or the script of `address` if it is one of the key's P2PK addresses
+fn owned_script(address: Option<&str>, public_key: &PublicKey, prefix: Prefix) -> Result<Vec<u8>, KisrError> {
+        let (schnorr_spk, ecdsa_spk) = p2pk_scripts(public_key);
+        let Some(a) = address.map(str::trim) else { return Ok(schnorr_spk); };
+        let addr = Address::try_from(a).map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, format!("invalid address {}", a)).with("address", a))?;
+        if addr.prefix != prefix { return Err(KisrError::new(KisrErrorCode::NetworkMismatch, format!("address {} is not on the sweep's network", a)).with("address", a)); }
+        let spk = pay_to_address_script(&addr).script().to_vec();
+        if spk != schnorr_spk && spk != ecdsa_spk { return Err(KisrError::new(KisrErrorCode::NoMatchingKey, format!("address {} is not a P2PK address of the signing key", a)).with("address", a)); }
+        Ok(spk)
+}
+
+fn resolve_target(t: &CancelTarget, network_byte: u8, public_key: &PublicKey, prefix: Prefix) -> Result<TxGenUtxo, KisrError> {
+        let (txid_hex, index, amount, coinbase_score, address) = match t {
+                CancelTarget::Outpoint { transaction_id, index, amount_sompi, is_coinbase, block_daa_score, address } => {
+                        let invalid = |field: &str| KisrError::new(KisrErrorCode::InvalidArgument, format!("invalid {} for {}:{}", field, transaction_id, index))
+                                .with("transactionId", transaction_id.as_str()).with("index", *index).with("field", field);
+                        let amount = parse_amount(amount_sompi).filter(|&a| a > 0).ok_or_else(|| invalid("amountSompi"))?;
+                        let coinbase_score = if *is_coinbase { Some(block_daa_score.as_ref().and_then(parse_amount).ok_or_else(|| invalid("blockDaaScore"))?) } else { None };
+                        (transaction_id.clone(), *index, amount, coinbase_score, address)
+                }
+                CancelTarget::Anchor { code, envelope_hex, address } => {
+                        let invite = Invite::open(code, &Envelope::from_hex(envelope_hex)?)?;
+                        if invite.payload.network != network_byte { return Err(KisrError::new(KisrErrorCode::NetworkMismatch, format!("invite {} belongs to another network", invite.code)).with("code", invite.code.as_str())); }
+                        (invite.payload.outpoint.transaction_id.clone(), invite.payload.outpoint.index, invite.payload.amount, None, address)
+                }
+                CancelTarget::AnchorTxid { anchor_txid, .. } => return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("anchor {} needs a node to resolve; use kaspa_kisr_cancel_invite_rpc or pass envelopeHex", anchor_txid))
+                        .with("anchorTxid", anchor_txid.as_str())),
+        };
+        let mut utxo = TxGenUtxo::new(parse_txid(&txid_hex)?, index, amount, owned_script(address.as_deref(), public_key, prefix)?);
+        if let Some(score) = coinbase_score {
+                utxo.is_coinbase = true;
+                utxo.block_daa_score = score;
+        }
+        Ok(utxo)
+}
+
+// Sweeps every listed KISRUTXO back to the inviter's own address (spec/protocol.md §6). Once broadcast, the presignatures are dead.
+pub(crate) fn cancel_invites(private_key_hex: &str, targets_json: &str, network: &str, fee_rate: i64) -> Result<crate::SafeJsonTx, KisrError> {
+        let network_id = parse_network_id(network.trim()).ok_or_else(|| KisrError::new(KisrErrorCode::InvalidNetwork, "invalid network").with("network", network.trim()))?;
+        let (targets, virtual_daa_score) = parse_targets(targets_json)?;
+        sweep_targets(private_key_hex, &targets, network_id, fee_rate, virtual_daa_score)
+}
+
+// The targets and the virtual DAA score given with them, if any
+pub(crate) fn parse_targets(targets_json: &str) -> Result<(Vec<CancelTarget>, Option<u64>), KisrError> {
+        let request: CancelRequest = serde_json::from_str(targets_json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid invites json: {}", e)))?;
+        match request {
+                CancelRequest::Targets(targets) => Ok((targets, None)),
+                CancelRequest::WithScore { invites, virtual_daa_score: None } => Ok((invites, None)),
+                CancelRequest::WithScore { invites, virtual_daa_score: Some(v) } => {
+                        let score = parse_amount(&v).ok_or_else(|| KisrError::new(KisrErrorCode::InvalidArgument, "invalid virtualDaaScore").with("field", "virtualDaaScore"))?;
+                        Ok((invites, Some(score)))
+                }
+        }
+}
+
+// The KISRUTXOs the targets name, once each
+pub(crate) fn resolve_targets(private_key_hex: &str, targets: &[CancelTarget], network_id: NetworkId) -> Result<Vec<TxGenUtxo>, KisrError> {
+        let network_byte = network_byte_for(&network_id)
+                .ok_or_else(|| KisrError::new(KisrErrorCode::InvalidNetwork, format!("network {} is not supported by KISR", network_id)).with("network", network_id.to_string()))?;
+        if targets.is_empty() { return Err(KisrError::new(KisrErrorCode::InvalidArgument, "no invites to cancel")); }
+        let public_key = keypair_from_hex(private_key_hex).map_err(|e| KisrError::new(KisrErrorCode::InvalidKey, e))?.public_key();
+        let mut utxos: Vec<TxGenUtxo> = Vec::with_capacity(targets.len());
+        for t in targets.iter() {
+                let utxo = resolve_target(t, network_byte, &public_key, Prefix::from(network_id))?;
+                if utxos.iter().any(|u| u.txid == utxo.txid && u.index == utxo.index) { continue; }
+                utxos.push(utxo);
+        }
+        Ok(utxos)
+}
+
+pub(crate) fn sweep_targets(private_key_hex: &str, targets: &[CancelTarget], network_id: NetworkId, fee_rate: i64, virtual_daa_score: Option<u64>) -> Result<crate::SafeJsonTx, KisrError> {
+        sweep_utxos(private_key_hex, resolve_targets(private_key_hex, targets, network_id)?, network_id, fee_rate, virtual_daa_score)
+}
+
+// Each KISRUTXO is signed for its own script (Schnorr or ECDSA); `virtual_daa_score` decides whether coinbase
+// KISRUTXOs have matured
+pub(crate) fn sweep_utxos(private_key_hex: &str, utxos: Vec<TxGenUtxo>, network_id: NetworkId, fee_rate: i64, virtual_daa_score: Option<u64>) -> Result<crate::SafeJsonTx, KisrError> {
+        let keypair = keypair_from_hex(private_key_hex).map_err(|e| KisrError::new(KisrErrorCode::InvalidKey, e))?;
+        let self_address = schnorr_address(&keypair, Prefix::from(network_id));
+        let entry = TxGenEntry {
+                fee_rate,
+                change_address: Some(self_address.to_string()),
+                held: utxos.iter().map(|u| (u.txid, u.index)).collect(),
+                utxos,
+                virtual_daa_score,
+                ..TxGenEntry::new(network_id)
+        };
+        let signed = build_and_sign_with(&entry, &InputSigner::matched(&[private_key_hex.to_string()])?, SIGHASH_ALL)?;
+        if signed.outputs.is_empty() { return Err(KisrError::new(KisrErrorCode::InsufficientFunds, "invite amounts do not cover the sweep fee")); }
+        Ok(signed)
+}
+
+fn cancel_invite_json(private_key_hex: *const c_char, invites_json: *const c_char, network: *const c_char, fee_rate: i64) -> Result<String, KisrError> {
+        let sk_hex = c_str_arg(private_key_hex, "private_key_hex")?;
+        let json = c_str_arg(invites_json, "invites_json")?;
+        let net = c_str_arg(network, "network")?;
+        to_json_string(&cancel_invites(&sk_hex, &json, &net, fee_rate)?)
+}
+
+// Returns the signed sweep as SafeJSON; submit it with kaspa_rpc_submit_safe_json.
+#[no_mangle]
+pub extern "C" fn kaspa_kisr_cancel_invite(private_key_hex: *const c_char, invites_json: *const c_char, network: *const c_char, fee_rate_sompi_per_kilomass: i64) -> *mut c_char {
+        ffi_guard("kaspa_kisr_cancel_invite", || {
+                ffi_string("kaspa_kisr_cancel_invite", cancel_invite_json(private_key_hex, invites_json, network, fee_rate_sompi_per_kilomass))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+
+        const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+        const TXID: &str = "b1682dd4b211409c27f3de6f4865212b8d0eacc6f2da79d17a8ae529d4329fbf";
+
+        #[test]
+        fn coinbase_invites_need_the_virtual_daa_score() {
+                let target = serde_json::json!({ "transactionId": TXID, "index": 0, "amountSompi": "100000000", "isCoinbase": true, "blockDaaScore": "1000" });
+                let bare = serde_json::json!([target]).to_string();
+                assert_eq!(cancel_invites(KEY, &bare, "mainnet", 0).unwrap_err().code, KisrErrorCode::ImmatureCoinbase);
+                let with_score = serde_json::json!({ "invites": [target], "virtualDaaScore": "1000000" }).to_string();
+                let swept = cancel_invites(KEY, &with_score, "mainnet", 0).unwrap();
+                assert_eq!((swept.inputs.len(), swept.outputs.len()), (1, 1));
+                let missing_score = serde_json::json!([{ "transactionId": TXID, "index": 0, "amountSompi": 5, "isCoinbase": true }]).to_string();
+                assert_eq!(cancel_invites(KEY, &missing_score, "mainnet", 0).unwrap_err().context["field"], "blockDaaScore");
+        }
+
+        #[test]
+        fn ecdsa_funded_invites_are_swept_with_ecdsa() {
+                let public_key = keypair_from_hex(KEY).unwrap().public_key();
+                let ecdsa = Address::new(Prefix::Mainnet, kaspa_addresses::Version::PubKeyECDSA, &public_key.serialize()).to_string();
+                let targets = serde_json::json!([{ "transactionId": TXID, "index": 0, "amountSompi": "100000000", "address": ecdsa }]).to_string();
+                let swept = cancel_invites(KEY, &targets, "mainnet", 0).unwrap();
+                assert_eq!(swept.inputs[0].utxo.script_public_key, format!("0000{}", hex::encode(p2pk_scripts(&public_key).1)));
+                // A Schnorr signature would push exactly 65 bytes (signature plus sighash type); ECDSA pushes a DER signature
+                assert_ne!(hex::decode(&swept.inputs[0].signature_script).unwrap()[0], 65);
+
+                let other = crate::schnorr_address(&keypair_from_hex(&"02".repeat(32)).unwrap(), Prefix::Mainnet).to_string();
+                let targets = serde_json::json!([{ "transactionId": TXID, "index": 0, "amountSompi": "100000000", "address": other }]).to_string();
+                assert_eq!(cancel_invites(KEY, &targets, "mainnet", 0).unwrap_err().code, KisrErrorCode::NoMatchingKey);
+        }
+}
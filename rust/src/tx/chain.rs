@@ -0,0 +1,172 @@
+use std::os::raw::{c_char, c_int};
+
+use serde::Serialize;
+use kaspa_addresses as kaddr;
+use kaspa_txscript::pay_to_address_script;
+
+use crate::{keypair_from_hex, schnorr_address, SafeJsonTx};
+use crate::error::{ffi_guard, ffi_string, KisrError, KisrErrorCode};
+use crate::fee::MAX_STANDARD_TX_MASS;
+use super::generator::{build_and_sign_entry, c_str_arg, entry_snapshot, to_json_string, TxGenEntry, TxGenUtxo};
+use super::select::{select_inputs, SelectionStrategy};
+use super::sign::{ALGO_ECDSA, SIGHASH_ALL};
+
+// Upper bound on compounding rounds; each round at least halves the number of inputs
+const MAX_CHAIN_LENGTH: usize = 64;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainSummary {
+        transaction_count: usize,
+        compounding_count: usize,
+        inputs_consumed: usize,
+        total_fee: String,
+        total_mass: String,
+        final_transaction_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainResult {
+        transactions: Vec<SafeJsonTx>,
+        summary: ChainSummary,
+}
+
+fn sum_inputs(tx: &SafeJsonTx) -> u64 { tx.inputs.iter().filter_map(|i| i.utxo.amount.parse::<u64>().ok()).sum() }
+fn sum_outputs(tx: &SafeJsonTx) -> u64 { tx.outputs.iter().filter_map(|o| o.value.parse::<u64>().ok()).sum() }
+fn mass_of(tx: &SafeJsonTx) -> u64 { tx.mass.parse::<u64>().unwrap_or(u64::MAX) }
+
+// Compounds the smallest UTXOs into the change address: as many per transaction as fit under the mass limit
+fn compound_batch(entry: &TxGenEntry, pool: &[TxGenUtxo], private_key_hex: &str, algo: u8) -> Result<(SafeJsonTx, usize), KisrError> {
+        let mut n = pool.len();
+        loop {
+                let batch = TxGenEntry {
+                        fee_rate: entry.fee_rate,
+                        change_address: entry.change_address.clone(),
+                        utxos: pool[..n].to_vec(),
+                        held: entry.held.clone(),
+                        virtual_daa_score: entry.virtual_daa_score,
+                        ..TxGenEntry::new(entry.network_id)
+                };
+                match build_and_sign_entry(&batch, private_key_hex, SIGHASH_ALL, algo) {
+                        Ok(tx) => {
+                                if tx.outputs.is_empty() { return Err(KisrError::new(KisrErrorCode::InsufficientFunds, "compounded UTXOs do not cover their own fee").with("inputCount", n)); }
+                                return Ok((tx, n));
+                        }
+                        Err(e) if e.code == KisrErrorCode::MassLimitExceeded => {
+                                if n <= 2 { return Err(KisrError { message: "a two-input compounding transaction exceeds the mass limit".to_string(), ..e }); }
+                                // Shrink proportionally to the overshoot, always by at least one input
+                                n = match e.context.get("mass").and_then(|m| m.as_u64()) {
+                                        Some(mass) => ((n as u128 * MAX_STANDARD_TX_MASS as u128 * 95 / 100 / mass as u128) as usize).clamp(2, n - 1),
+                                        None => (n / 2).max(2),
+                                };
+                        }
+                        Err(e) => return Err(e),
+                }
+        }
+}
+
+// Produces the compounding transactions (in submission order) followed by the final payment.
+pub(crate) fn build_and_sign_chain(entry: &TxGenEntry, private_key_hex: &str, sighash_type_u8: u8, algo: u8) -> Result<String, KisrError> {
+        // Input indices stop meaning anything once the pool is re-sorted and compounded
+        if !entry.input_sighash.is_empty() || entry.sign_indices.is_some() {
+                return Err(KisrError::new(KisrErrorCode::InvalidArgument, "per-input sighash types and partial signing are not supported when building a chain"));
+        }
+        let mut entry = entry.clone();
+        entry.utxos = select_inputs(&entry)?;
+        entry.candidates.clear();
+        entry.strategy = SelectionStrategy::None;
+        let entry = &entry;
+        if entry.utxos.is_empty() { return Err(KisrError::new(KisrErrorCode::BuildFailed, "no utxos")); }
+        let total_output: u64 = entry.outputs.iter().map(|o| o.amount).sum();
+        let total_input: u64 = entry.utxos.iter().map(|u| u.amount).sum();
+        if total_input < total_output {
+                return Err(KisrError::new(KisrErrorCode::InsufficientFunds, format!("insufficient funds: {} < {}", total_input, total_output))
+                        .with("available", total_input).with("totalOutput", total_output));
+        }
+
+        let mut transactions: Vec<SafeJsonTx> = Vec::new();
+        let mut pool: Vec<TxGenUtxo> = entry.utxos.clone();
+        loop {
+                let mut final_entry = entry.clone();
+                final_entry.utxos = pool.clone();
+                match build_and_sign_entry(&final_entry, private_key_hex, sighash_type_u8, algo) {
+                        Ok(tx) => { transactions.push(tx); break; }
+                        Err(e) if e.code == KisrErrorCode::MassLimitExceeded => {}
+                        Err(e) => return Err(e),
+                }
+                if pool.len() < 2 { return Err(KisrError::new(KisrErrorCode::MassLimitExceeded, "transaction cannot be built within the mass limit").with("maxMass", MAX_STANDARD_TX_MASS)); }
+                if transactions.len() >= MAX_CHAIN_LENGTH { return Err(KisrError::new(KisrErrorCode::MassLimitExceeded, "compounding chain too long").with("maxChainLength", MAX_CHAIN_LENGTH)); }
+
+                // Compounding outputs are re-spent by later transactions in the chain, so the key must own the change address
+                let change_str = entry.change_address.as_ref().ok_or_else(|| KisrError::new(KisrErrorCode::InvalidArgument, "compounding requires a change address"))?;
+                let change_addr = kaddr::Address::try_from(change_str.as_str()).map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, "invalid change address").with("address", change_str.as_str()))?;
+                let keypair = keypair_from_hex(private_key_hex).map_err(|e| KisrError::new(KisrErrorCode::InvalidKey, e))?;
+                let own_addr = if algo == ALGO_ECDSA {
+                        kaddr::Address::new(entry.prefix(), kaddr::Version::PubKeyECDSA, &keypair.public_key().serialize())
+                } else {
+                        schnorr_address(&keypair, entry.prefix())
+                };
+                if change_addr != own_addr { return Err(KisrError::new(KisrErrorCode::NoMatchingKey, "change address is not controlled by the signing key").with("address", change_str.as_str())); }
+
+                pool.sort_by_key(|u| u.amount);
+                let (tx, used) = compound_batch(entry, &pool, private_key_hex, algo)?;
+                let txid_bytes = hex::decode(&tx.id).map_err(|_| KisrError::new(KisrErrorCode::Internal, "invalid compounding txid"))?;
+                let mut txid = [0u8; 32];
+                txid.copy_from_slice(&txid_bytes);
+                let amount = tx.outputs[0].value.parse::<u64>().map_err(|_| KisrError::new(KisrErrorCode::Internal, "invalid compounding output"))?;
+                pool.drain(..used);
+                pool.push(TxGenUtxo::new(txid, 0, amount, pay_to_address_script(&change_addr).script().to_vec()));
+                transactions.push(tx);
+        }
+
+        let summary = ChainSummary {
+                transaction_count: transactions.len(),
+                compounding_count: transactions.len() - 1,
+                inputs_consumed: entry.utxos.len(),
+                total_fee: transactions.iter().map(|t| sum_inputs(t).saturating_sub(sum_outputs(t))).sum::<u64>().to_string(),
+                total_mass: transactions.iter().map(mass_of).sum::<u64>().to_string(),
+                final_transaction_id: transactions.last().map(|t| t.id.clone()).unwrap_or_default(),
+        };
+        to_json_string(&ChainResult { transactions, summary })
+}
+
+// Returns `{transactions: [SafeJsonTx...], summary}`; submit the transactions in order.
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_build_and_sign_chain_safejson(gen: c_int, private_key_hex: *const c_char, sighash_type: u8, algo: u8) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_build_and_sign_chain_safejson", || {
+                let result = c_str_arg(private_key_hex, "private_key_hex")
+                        .and_then(|sk_hex| build_and_sign_chain(&entry_snapshot(gen)?, &sk_hex, sighash_type, algo));
+                ffi_string("kaspa_tx_generator_build_and_sign_chain_safejson", result)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+        use kaspa_addresses::Prefix;
+        use kaspa_consensus_core::network::{NetworkId, NetworkType};
+        use super::super::generator::TxGenOutput;
+
+        const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+        #[test]
+        fn compounds_mature_coinbase_utxos() {
+                let address = crate::schnorr_address(&keypair_from_hex(KEY).unwrap(), Prefix::Mainnet);
+                let spk = pay_to_address_script(&address).script().to_vec();
+                let utxos: Vec<TxGenUtxo> = (0..150u32).map(|i| TxGenUtxo { block_daa_score: 1_000, is_coinbase: true, ..TxGenUtxo::new([(i % 251) as u8; 32], i, 100_000_000, spk.clone()) }).collect();
+                let mut entry = TxGenEntry {
+                        change_address: Some(address.to_string()),
+                        outputs: vec![TxGenOutput { address: address.to_string(), amount: 10_000_000_000 }],
+                        utxos,
+                        virtual_daa_score: Some(1_000_000),
+                        ..TxGenEntry::new(NetworkId::new(NetworkType::Mainnet))
+                };
+                let result: serde_json::Value = serde_json::from_str(&build_and_sign_chain(&entry, KEY, SIGHASH_ALL, 0).unwrap()).unwrap();
+                assert!(result["summary"]["compoundingCount"].as_u64().unwrap() >= 1);
+                assert_eq!(result["summary"]["inputsConsumed"], 150);
+
+                entry.sign_indices = Some([0].into_iter().collect());
+                assert_eq!(build_and_sign_chain(&entry, KEY, SIGHASH_ALL, 0).unwrap_err().code, KisrErrorCode::InvalidArgument);
+        }
+}
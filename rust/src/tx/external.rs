@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_int};
+
+use kaspa_consensus_core::hashing::sighash::SigHashReusedValuesUnsync;
+use kaspa_consensus_core::tx::SignableTransaction;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ffi_guard, ffi_string, KisrError, KisrErrorCode};
+use super::generator::{build_and_sign_with, c_str_arg, entry_snapshot, to_json_string, TxGenEntry};
+use super::plan::TxPlan;
+use super::sign::{input_message, p2pk_kind, InputSigner, KaspaSignCallback, ALGO_ECDSA, ALGO_SCHNORR};
+
+// Signing with keys that never enter the process: either a callback signs each input during the build, or the
+// caller fetches the sighashes, signs them elsewhere and hands the signatures back. The crate assembles the
+// signature scripts and checks every signature against its UTXO.
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InputSighash {
+        index: u32,
+        algo: u8,
+        sighash_type: u8,
+        sighash: String,
+        script_public_key: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Sighashes {
+        transaction_id: String,
+        inputs: Vec<InputSighash>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProvidedSignatures {
+        transaction_id: String,
+        signatures: Vec<ProvidedSignature>,
+}
+
+#[derive(Deserialize)]
+struct ProvidedSignature {
+        index: u32,
+        signature: String,
+}
+
+fn sighashes(entry: &TxGenEntry, sighash_type_u8: u8) -> Result<String, KisrError> {
+        let tx_plan = TxPlan::new(entry)?;
+        let plan = tx_plan.sign_plan(sighash_type_u8)?;
+        let (tx, entries) = (&tx_plan.tx, &tx_plan.entries);
+        let signable = SignableTransaction::with_entries(tx.clone(), entries.clone());
+        let mut reused = SigHashReusedValuesUnsync::new();
+        let mut inputs = Vec::new();
+        for (i, e) in entries.iter().enumerate() {
+                if !plan.signs(i) { continue; }
+                let script = e.script_public_key.script();
+                let ecdsa = p2pk_kind(script).ok_or_else(|| KisrError::new(KisrErrorCode::NoMatchingKey, format!("input {} is not a P2PK output", i)).with("inputIndex", i))?;
+                let sig_type = plan.sig_type(i);
+                let msg = input_message(&signable, i, sig_type, ecdsa, &mut reused)?;
+                inputs.push(InputSighash {
+                        index: i as u32,
+                        algo: if ecdsa { ALGO_ECDSA } else { ALGO_SCHNORR },
+                        sighash_type: sig_type.to_u8(),
+                        sighash: hex::encode(msg.as_ref()),
+                        script_public_key: format!("{:04x}{}", e.script_public_key.version(), hex::encode(script)),
+                });
+        }
+        to_json_string(&Sighashes { transaction_id: tx.id().to_string(), inputs })
+}
+
+fn sign_with_signatures(entry: &TxGenEntry, sighash_type_u8: u8, json: &str) -> Result<String, KisrError> {
+        let provided: ProvidedSignatures = serde_json::from_str(json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid signatures json: {}", e)))?;
+        // The handle must still describe the transaction the sighashes were taken from
+        let tx_plan = TxPlan::new(entry)?;
+        let current = tx_plan.tx.id().to_string();
+        if !current.eq_ignore_ascii_case(provided.transaction_id.trim()) {
+                return Err(KisrError::new(KisrErrorCode::StaleTransaction, format!("transaction changed since the sighashes were taken ({} != {})", current, provided.transaction_id))
+                        .with("transactionId", current).with("providedTransactionId", provided.transaction_id));
+        }
+        let mut sigs = HashMap::with_capacity(provided.signatures.len());
+        for s in provided.signatures.into_iter() {
+                let bytes = hex::decode(s.signature.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, format!("invalid signature hex for input {}", s.index)).with("inputIndex", s.index))?;
+                sigs.insert(s.index as usize, bytes);
+        }
+        to_json_string(&tx_plan.sign(&InputSigner::Provided(sigs), &tx_plan.sign_plan(sighash_type_u8)?)?)
+}
+
+// Returns `{transactionId, inputs: [{index, algo, sighashType, sighash, scriptPublicKey}]}` for every input to be
+// signed (see kaspa_tx_generator_set_sign_indices). Sign each 32-byte sighash with the key owning the script.
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_sighashes(gen: c_int, sighash_type: u8) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_sighashes", || {
+                ffi_string("kaspa_tx_generator_sighashes", entry_snapshot(gen).and_then(|entry| sighashes(&entry, sighash_type)))
+        })
+}
+
+// `signatures_json` is `{transactionId, signatures: [{index, signature}]}` with hex signatures over the sighashes
+// from kaspa_tx_generator_sighashes; fails if the handle's transaction has changed since.
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_build_and_sign_safejson_with_signatures(gen: c_int, sighash_type: u8, signatures_json: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_build_and_sign_safejson_with_signatures", || {
+                let result = c_str_arg(signatures_json, "signatures_json")
+                        .and_then(|json| sign_with_signatures(&entry_snapshot(gen)?, sighash_type, &json));
+                ffi_string("kaspa_tx_generator_build_and_sign_safejson_with_signatures", result)
+        })
+}
+
+// Calls `callback` once per input to be signed, on the calling thread, before returning
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_build_and_sign_safejson_with_callback(gen: c_int, sighash_type: u8, callback: Option<KaspaSignCallback>, user_data: *mut c_void) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_build_and_sign_safejson_with_callback", || {
+                let result = callback
+                        .ok_or_else(|| KisrError::new(KisrErrorCode::NullArgument, "null callback").with("argument", "callback"))
+                        .and_then(|callback| {
+                                let signer = InputSigner::External { callback, user_data };
+                                to_json_string(&build_and_sign_with(&entry_snapshot(gen)?, &signer, sighash_type)?)
+                        });
+                ffi_string("kaspa_tx_generator_build_and_sign_safejson_with_callback", result)
+        })
+}
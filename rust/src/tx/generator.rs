@@ -1,39 +1,102 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Mutex, atomic::{AtomicI32, Ordering}, OnceLock};
-use std::ffi::{CStr, CString};
+use std::ffi::CStr;
 use std::os::raw::{c_char, c_int};
-use std::ptr;
 use kaspa_addresses as kaddr;
-use kaspa_txscript::pay_to_address_script;
 
-use crate::{KaspaUtxoEntry, KaspaOutputEntry, SafeJsonInput, SafeJsonInputUtxo, SafeJsonOutput, SafeJsonTx, decode_spk_hex_strip_optional_version_prefix};
+use kaspa_consensus_core::config::params::Params;
+use kaspa_consensus_core::network::{NetworkId, NetworkType};
 
-#[derive(Clone)]
-pub(crate) struct TxGenUtxo {
-        pub(crate) txid: [u8;32],
-        pub(crate) index: u32,
-        pub(crate) amount: u64,
-        pub(crate) spk_bytes: Vec<u8>,
+use serde::Deserialize;
+
+use crate::{KaspaUtxoEntry, KaspaUtxoEntryV2, KaspaOutputEntry, SafeJsonTx, decode_spk_hex_version_prefix, decode_versioned_spk_hex};
+use crate::kisr::TxGenerator;
+use crate::error::{fail, ffi_guard, ffi_status, ffi_string, lock_unpoisoned, KisrError, KisrErrorCode};
+use super::select::SelectionStrategy;
+use super::reserve::{parse_outpoint, OutpointKey};
+use super::sign::InputSigner;
+use super::plan::TxPlan;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxGenUtxo {
+        pub txid: [u8;32],
+        pub index: u32,
+        pub amount: u64,
+        pub spk_bytes: Vec<u8>,
+        pub script_version: u16,
+        pub block_daa_score: u64,
+        pub is_coinbase: bool,
 }
 
-#[derive(Clone)]
-pub(crate) struct TxGenOutput {
-        pub(crate) address: String,
-        pub(crate) amount: u64,
+impl TxGenUtxo {
+        // Version 0, not coinbase, DAA score unknown: enough for outputs the wallet created itself (compounding, sweeps)
+        pub fn new(txid: [u8;32], index: u32, amount: u64, spk_bytes: Vec<u8>) -> Self {
+                TxGenUtxo { txid, index, amount, spk_bytes, script_version: 0, block_daa_score: 0, is_coinbase: false }
+        }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxGenOutput {
+        pub address: String,
+        pub amount: u64,
 }
 
 #[derive(Clone)]
 pub(crate) struct TxGenEntry {
-        pub(crate) is_testnet: bool,
+        pub(crate) network_id: NetworkId,
         pub(crate) fee_rate: i64,
         pub(crate) change_address: Option<String>,
         pub(crate) utxos: Vec<TxGenUtxo>,
         pub(crate) outputs: Vec<TxGenOutput>,
         pub(crate) payload: Vec<u8>,
+        // Pool the selection strategy draws from, on top of the UTXOs that are always spent
+        pub(crate) candidates: Vec<TxGenUtxo>,
+        pub(crate) strategy: SelectionStrategy,
+        pub(crate) excluded: HashSet<OutpointKey>,
+        // Reserved outpoints this build may still spend: its own reservation, or the KISRUTXOs the flow holds
+        pub(crate) held: HashSet<OutpointKey>,
+        // Needed to tell whether coinbase UTXOs have matured
+        pub(crate) virtual_daa_score: Option<u64>,
+        // Per input index, overriding the sighash type passed to the build call
+        pub(crate) input_sighash: HashMap<u32, u8>,
+        // When set, only these input indices are signed (partial signing)
+        pub(crate) sign_indices: Option<HashSet<u32>>,
 }
 
-pub(crate) fn __gens() -> &'static Mutex<HashMap<i32, TxGenEntry>> {
-        static MAP: OnceLock<Mutex<HashMap<i32, TxGenEntry>>> = OnceLock::new();
+impl TxGenEntry {
+        pub(crate) fn new(network_id: NetworkId) -> Self {
+                TxGenEntry {
+                        network_id,
+                        fee_rate: 0,
+                        change_address: None,
+                        utxos: Vec::new(),
+                        outputs: Vec::new(),
+                        payload: Vec::new(),
+                        candidates: Vec::new(),
+                        strategy: SelectionStrategy::None,
+                        excluded: HashSet::new(),
+                        held: HashSet::new(),
+                        virtual_daa_score: None,
+                        input_sighash: HashMap::new(),
+                        sign_indices: None,
+                }
+        }
+
+        // Addresses and mass parameters both follow the handle's network
+        pub(crate) fn prefix(&self) -> kaddr::Prefix { kaddr::Prefix::from(self.network_id) }
+        pub(crate) fn params(&self) -> Params { self.network_id.into() }
+
+        pub(crate) fn rate(&self) -> u64 { if self.fee_rate <= 0 { 1000 } else { self.fee_rate as u64 } }
+
+        pub(crate) fn is_mature(&self, u: &TxGenUtxo) -> bool {
+                if !u.is_coinbase { return true; }
+                let maturity = self.params().coinbase_maturity().after();
+                self.virtual_daa_score.is_some_and(|v| v >= u.block_daa_score.saturating_add(maturity))
+        }
+}
+
+pub(crate) fn __gens() -> &'static Mutex<HashMap<i32, TxGenerator>> {
+        static MAP: OnceLock<Mutex<HashMap<i32, TxGenerator>>> = OnceLock::new();
         MAP.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
@@ -43,448 +106,447 @@ fn __next_gen_handle() -> i32 {
         next.fetch_add(1, Ordering::SeqCst)
 }
 
+fn invalid_handle(handle: c_int) -> KisrError {
+        KisrError::new(KisrErrorCode::InvalidHandle, "invalid handle").with("handle", handle)
+}
+
+fn with_gen<R>(handle: c_int, f: impl FnOnce(&mut TxGenerator) -> Result<R, KisrError>) -> Result<R, KisrError> {
+        let mut m = lock_unpoisoned(__gens());
+        let gen = m.get_mut(&handle).ok_or_else(|| invalid_handle(handle))?;
+        f(gen)
+}
+
+// Builds work on a copy so the registry lock is not held during selection and signing
+pub(crate) fn gen_snapshot(handle: c_int) -> Result<TxGenerator, KisrError> {
+        lock_unpoisoned(__gens()).get(&handle).cloned().ok_or_else(|| invalid_handle(handle))
+}
+
+pub(crate) fn entry_snapshot(handle: c_int) -> Result<TxGenEntry, KisrError> {
+        gen_snapshot(handle).map(|g| g.entry)
+}
+
+pub(crate) fn c_str_arg(p: *const c_char, name: &str) -> Result<String, KisrError> {
+        if p.is_null() { return Err(KisrError::new(KisrErrorCode::NullArgument, format!("null {}", name)).with("argument", name)); }
+        Ok(unsafe { CStr::from_ptr(p) }.to_string_lossy().to_string())
+}
+
+pub(crate) fn parse_address_for(entry: &TxGenEntry, s: &str) -> Result<kaddr::Address, KisrError> {
+        let addr = kaddr::Address::try_from(s).map_err(|_| KisrError::new(KisrErrorCode::InvalidAddress, format!("invalid address {}", s)).with("address", s))?;
+        if addr.prefix != entry.prefix() {
+                return Err(KisrError::new(KisrErrorCode::NetworkMismatch, format!("address {} is not for network {}", s, entry.network_id)).with("address", s).with("network", entry.network_id.to_string()));
+        }
+        Ok(addr)
+}
+
+pub(crate) fn to_json_string<T: serde::Serialize>(value: &T) -> Result<String, KisrError> {
+        serde_json::to_string(value).map_err(|e| KisrError::new(KisrErrorCode::Serialization, format!("serialization error: {}", e)))
+}
+
 pub(crate) fn tx_generator_new(is_testnet: bool) -> c_int {
-        let handle = __next_gen_handle();
-        let entry = TxGenEntry {
-                is_testnet,
-                fee_rate: 0,
-                change_address: None,
-                utxos: Vec::new(),
-                outputs: Vec::new(),
-                payload: Vec::new(),
-        };
-        let map = __gens();
-        let mut m = map.lock().unwrap();
-        m.insert(handle, entry);
-        handle
+        let network_id = if is_testnet { NetworkId::with_suffix(NetworkType::Testnet, 10) } else { NetworkId::new(NetworkType::Mainnet) };
+        tx_generator_new_for_network(network_id)
 }
 
-pub(crate) fn tx_generator_free(handle: c_int) -> c_int {
-        let mut m = __gens().lock().unwrap();
-        if m.remove(&(handle as i32)).is_some() { 0 } else { -1 }
+pub(crate) fn tx_generator_new_with_network(network: *const c_char) -> Result<c_int, KisrError> {
+        let s = c_str_arg(network, "network")?;
+        Ok(tx_generator_insert(TxGenerator::for_network(&s)?))
 }
 
-pub(crate) fn tx_generator_clear(handle: c_int) -> c_int {
-        let mut m = __gens().lock().unwrap();
-        if let Some(entry) = m.get_mut(&(handle as i32)) {
-                entry.utxos.clear();
-                entry.outputs.clear();
-                entry.change_address = None;
-                entry.payload.clear();
-                0
-        } else { -1 }
+pub(crate) fn tx_generator_new_for_network(network_id: NetworkId) -> c_int {
+        tx_generator_insert(TxGenerator::new(network_id))
 }
 
-pub(crate) fn tx_generator_set_change_address(handle: c_int, address: *const c_char) -> c_int {
-        if address.is_null() { return -1; }
-        let s = unsafe { CStr::from_ptr(address) }.to_string_lossy().to_string();
-        let mut m = __gens().lock().unwrap();
-        let Some(entry) = m.get_mut(&(handle as i32)) else { return -2; };
-        let addr = match kaddr::Address::try_from(s.as_str()) { Ok(a) => a, Err(_) => return -3 };
-        let expected_prefix = if entry.is_testnet { kaddr::Prefix::Testnet } else { kaddr::Prefix::Mainnet };
-        if addr.prefix != expected_prefix { return -4; }
-        entry.change_address = Some(s);
-        0
+fn tx_generator_insert(gen: TxGenerator) -> c_int {
+        let handle = __next_gen_handle();
+        lock_unpoisoned(__gens()).insert(handle, gen);
+        handle
 }
 
-pub(crate) fn tx_generator_set_fee_rate(handle: c_int, fee_rate_sompi_per_kilomass: i64) -> c_int {
-        let mut m = __gens().lock().unwrap();
-        if let Some(entry) = m.get_mut(&(handle as i32)) {
-                entry.fee_rate = fee_rate_sompi_per_kilomass;
-                0
-        } else { -1 }
+pub(crate) fn tx_generator_free(handle: c_int) -> Result<(), KisrError> {
+        let mut m = lock_unpoisoned(__gens());
+        m.remove(&handle).map(|_| ()).ok_or_else(|| invalid_handle(handle))
 }
 
-pub(crate) fn tx_generator_set_payload_hex(handle: c_int, payload_hex: *const c_char) -> c_int {
-        let mut m = __gens().lock().unwrap();
-        let Some(entry) = m.get_mut(&(handle as i32)) else { return -1; };
-        if payload_hex.is_null() { entry.payload.clear(); return 0; }
-        let s = unsafe { CStr::from_ptr(payload_hex) }.to_string_lossy().to_string();
-        let t = s.trim();
-        if t.is_empty() { entry.payload.clear(); return 0; }
-        match hex::decode(t) { Ok(v) => { entry.payload = v; 0 }, Err(_) => -2 }
+pub(crate) fn tx_generator_clear(handle: c_int) -> Result<(), KisrError> {
+        with_gen(handle, |gen| { gen.clear(); Ok(()) })
 }
 
-pub(crate) fn tx_generator_add_utxo(handle: c_int, utxo_ptr: *const KaspaUtxoEntry) -> c_int {
-        if utxo_ptr.is_null() { return -1; }
-        let utxo = unsafe { &*utxo_ptr };
-        if utxo.txid_be_hex.is_null() || utxo.script_pub_key_hex.is_null() { return -2; }
-        let txid_str = unsafe { CStr::from_ptr(utxo.txid_be_hex) }.to_string_lossy().to_string();
-        let spk_hex = unsafe { CStr::from_ptr(utxo.script_pub_key_hex) }.to_string_lossy().to_string();
-        let txid_bytes = match hex::decode(txid_str.trim()) { Ok(v) => v, Err(_) => return -3 };
-        if txid_bytes.len() != 32 { return -4; }
-        let mut txid_arr = [0u8;32]; txid_arr.copy_from_slice(&txid_bytes);
-        let spk_bytes = match decode_spk_hex_strip_optional_version_prefix(&spk_hex) { Ok(v) => v, Err(_) => return -5 };
-        let mut m = __gens().lock().unwrap();
-        let Some(entry) = m.get_mut(&(handle as i32)) else { return -6; };
-        entry.utxos.push(TxGenUtxo { txid: txid_arr, index: utxo.index, amount: utxo.amount, spk_bytes });
-        0
-}
-
-pub(crate) fn tx_generator_add_output(handle: c_int, output_ptr: *const KaspaOutputEntry) -> c_int {
-        if output_ptr.is_null() { return -1; }
-        let output = unsafe { &*output_ptr };
-        if output.address.is_null() { return -2; }
-        let addr_str = unsafe { CStr::from_ptr(output.address) }.to_string_lossy().to_string();
-        let mut m = __gens().lock().unwrap();
-        let Some(entry) = m.get_mut(&(handle as i32)) else { return -3; };
-        let addr = match kaddr::Address::try_from(addr_str.as_str()) { Ok(a) => a, Err(_) => return -4 };
-        let expected_prefix = if entry.is_testnet { kaddr::Prefix::Testnet } else { kaddr::Prefix::Mainnet };
-        if addr.prefix != expected_prefix { return -5; }
-        entry.outputs.push(TxGenOutput { address: addr_str, amount: output.amount });
-        0
-}
-
-pub(crate) fn tx_generator_build_unsigned_safejson(gen: c_int) -> *mut c_char {
-        use kaspa_consensus_core::tx::{Transaction, TransactionInput, TransactionOutpoint, TransactionOutput, ScriptPublicKey, UtxoEntry, SignableTransaction};
-        use kaspa_consensus_core::subnets::SubnetworkId;
-        let (is_testnet, fee_rate, change_addr_opt, utxos, outs, payload) = {
-                let g = __gens().lock().unwrap();
-                let Some(entry) = g.get(&gen) else { return ptr::null_mut(); };
-                (entry.is_testnet, entry.fee_rate, entry.change_address.clone(), entry.utxos.clone(), entry.outputs.clone(), entry.payload.clone())
-        };
-        let default_rate: u64 = 1000;
-        let rate = if fee_rate <= 0 { default_rate } else { fee_rate as u64 };
-        let total_input: u64 = utxos.iter().map(|u| u.amount).sum();
-        let total_output_user: u64 = outs.iter().map(|o| o.amount).sum();
-        let assumed_sig_script_len: usize = 66;
-        let build_tx = |include_change: bool, change_amount: u64| -> Option<(Transaction, Vec<UtxoEntry>, Vec<(String,u32)>, Vec<String>, Vec<Vec<u8>>)> {
-                let mut inputs: Vec<TransactionInput> = Vec::with_capacity(utxos.len());
-                let mut entries: Vec<UtxoEntry> = Vec::with_capacity(utxos.len());
-                let mut prev_outpoints: Vec<(String,u32)> = Vec::with_capacity(utxos.len());
-                let mut utxo_scripts_hex: Vec<String> = Vec::with_capacity(utxos.len());
-                let mut outputs_spk_bytes: Vec<Vec<u8>> = Vec::with_capacity(outs.len() + if include_change { 1 } else { 0 });
-                for u in utxos.iter() {
-                        let spk = ScriptPublicKey::new(0, u.spk_bytes.clone().into());
-                        let input = TransactionInput::new(TransactionOutpoint { transaction_id: u.txid.into(), index: u.index }, vec![], 0, 1);
-                        inputs.push(input);
-                        entries.push(UtxoEntry::new(u.amount, spk.clone(), 0, false));
-                        let txid_be_hex_str = hex::encode(u.txid);
-                        prev_outpoints.push((txid_be_hex_str, u.index));
-                        utxo_scripts_hex.push(hex::encode(&u.spk_bytes));
-                }
-                let mut outputs: Vec<TransactionOutput> = Vec::with_capacity(outs.len() + if include_change { 1 } else { 0 });
-                for o in outs.iter() {
-                        let addr = kaddr::Address::try_from(o.address.as_str()).ok()?;
-                        let expected_prefix = if is_testnet { kaddr::Prefix::Testnet } else { kaddr::Prefix::Mainnet };
-                        if addr.prefix != expected_prefix { return None; }
-                        let spk = pay_to_address_script(&addr);
-                        outputs_spk_bytes.push(spk.script().to_vec());
-                        outputs.push(TransactionOutput { value: o.amount, script_public_key: spk });
-                }
-                if include_change {
-                        let change_addr = kaddr::Address::try_from(change_addr_opt.as_ref()?.as_str()).ok()?;
-                        let spk = pay_to_address_script(&change_addr);
-                        outputs_spk_bytes.push(spk.script().to_vec());
-                        outputs.push(TransactionOutput { value: change_amount, script_public_key: spk });
-                }
-                let mut tx = Transaction::new(0, inputs, outputs, 0, SubnetworkId::default(), 0, payload.clone());
-                for inp in tx.inputs.iter_mut() { inp.signature_script = vec![0u8; assumed_sig_script_len]; }
-                tx.finalize();
-                Some((tx, entries, prev_outpoints, utxo_scripts_hex, outputs_spk_bytes))
-        };
-        let (tx0, entries0, _prev0, _spkhex0, _outspk0) = match build_tx(false, 0) { Some(v) => v, None => return ptr::null_mut() };
-        let mass0 = {
-                use kaspa_consensus_core::config::params::Params;
-                use kaspa_consensus_core::network::NetworkType;
-                use kaspa_consensus_core::mass::MassCalculator;
-                let params: Params = NetworkType::Mainnet.into();
-                let mc = MassCalculator::new_with_consensus_params(&params);
-                let non = mc.calc_non_contextual_masses(&tx0);
-                let ctx = mc.calc_contextual_masses(&(&SignableTransaction::with_entries(tx0.clone(), entries0.clone())).as_verifiable()).unwrap_or(kaspa_consensus_core::mass::ContextualMasses::new(0));
-                ctx.max(non)
-        } as u64;
-        let min_fee0 = ((mass0 as u128) * (rate as u128) + 999) / 1000;
-        let change = (total_input as i128) - (total_output_user as i128) - (min_fee0 as i128);
-        let include_change = change_addr_opt.is_some() && change > 0;
-        let (mut tx, mut entries, prev_outpoints, utxo_scripts_hex, outputs_spk_bytes) = if include_change {
-                let change_u = change as u64;
-                match build_tx(true, change_u) { Some(v) => v, None => return ptr::null_mut() }
+pub(crate) fn tx_generator_set_change_address(handle: c_int, address: *const c_char) -> Result<(), KisrError> {
+        let s = c_str_arg(address, "address")?;
+        with_gen(handle, |gen| gen.change_address(&s).map(|_| ()))
+}
+
+pub(crate) fn tx_generator_set_fee_rate(handle: c_int, fee_rate_sompi_per_kilomass: i64) -> Result<(), KisrError> {
+        with_gen(handle, |gen| { gen.fee_rate(fee_rate_sompi_per_kilomass); Ok(()) })
+}
+
+pub(crate) fn tx_generator_set_payload_hex(handle: c_int, payload_hex: *const c_char) -> Result<(), KisrError> {
+        let payload = if payload_hex.is_null() {
+                Vec::new()
         } else {
-                (tx0, entries0, _prev0, _spkhex0, _outspk0)
-        };
-        for inp in tx.inputs.iter_mut() { inp.signature_script.clear(); }
-        let id = tx.id().to_string();
-        let mass = {
-                use kaspa_consensus_core::config::params::Params;
-                use kaspa_consensus_core::network::NetworkType;
-                use kaspa_consensus_core::mass::{MassCalculator};
-                let params: Params = NetworkType::Mainnet.into();
-                let mc = MassCalculator::new_with_consensus_params(&params);
-                let non = mc.calc_non_contextual_masses(&tx);
-                let ctx = mc.calc_contextual_masses(&(&SignableTransaction::with_entries(tx.clone(), entries.clone())).as_verifiable()).unwrap_or(kaspa_consensus_core::mass::ContextualMasses::new(0));
-                ctx.max(non)
+                let s = unsafe { CStr::from_ptr(payload_hex) }.to_string_lossy().to_string();
+                hex::decode(s.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid payload hex"))?
         };
-        let mut inputs_json: Vec<SafeJsonInput> = Vec::with_capacity(tx.inputs.len());
-        for (i, inp) in tx.inputs.iter().enumerate() {
-                let (txid_be_hex, index) = &prev_outpoints[i];
-                let utxo_spk_prefixed = format!("0000{}", utxo_scripts_hex[i].to_lowercase());
-                let utxo_amount_str = utxos[i].amount.to_string();
-                let utxo_block_daa_score_str = "0".to_string();
-                let utxo_is_coinbase = false;
-                inputs_json.push(SafeJsonInput {
-                        transaction_id: txid_be_hex.clone(),
-                        index: *index,
-                        signature_script: hex::encode(&inp.signature_script),
-                        sequence: inp.sequence.to_string(),
-                        sig_op_count: 1,
-                        utxo: SafeJsonInputUtxo {
-                                address: None,
-                                amount: utxo_amount_str,
-                                script_public_key: utxo_spk_prefixed,
-                                block_daa_score: utxo_block_daa_score_str,
-                                is_coinbase: utxo_is_coinbase,
-                        },
-                });
-        }
-        let mut outputs_json: Vec<SafeJsonOutput> = Vec::with_capacity(tx.outputs.len());
-        for (idx, out) in tx.outputs.iter().enumerate() {
-                let spk_hex = hex::encode(&outputs_spk_bytes[idx].as_slice());
-                let spk_prefixed = format!("0000{}", spk_hex);
-                outputs_json.push(SafeJsonOutput { value: out.value.to_string(), script_public_key: spk_prefixed });
+        with_gen(handle, |gen| { gen.payload(payload); Ok(()) })
+}
+
+pub(crate) fn parse_txid(txid_hex: &str) -> Result<[u8; 32], KisrError> {
+        let bytes = hex::decode(txid_hex.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, format!("invalid transaction id {}", txid_hex.trim())).with("transactionId", txid_hex.trim()))?;
+        if bytes.len() != 32 {
+                return Err(KisrError::new(KisrErrorCode::InvalidLength, format!("transaction id {} must be 32 bytes", txid_hex.trim())).with("transactionId", txid_hex.trim()));
         }
-        let safe = SafeJsonTx {
-                id,
-                inputs: inputs_json,
-                outputs: outputs_json,
-                version: tx.version,
-                lock_time: tx.lock_time.to_string(),
-                gas: tx.gas.to_string(),
-                subnetwork_id: format!("{:040}", 0),
-                payload: hex::encode(&payload),
-                mass: mass.to_string(),
-        };
-        match serde_json::to_string(&safe) { Ok(s) => CString::new(s).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()), Err(_) => ptr::null_mut() }
-}
-
-pub(crate) fn tx_generator_build_and_sign_safejson_with_type_and_algo(gen: c_int, private_key_hex: *const c_char, sighash_type_u8: u8, algo: u8) -> *mut c_char {
-        use kaspa_consensus_core::tx::{Transaction, TransactionInput, TransactionOutpoint, TransactionOutput, ScriptPublicKey, UtxoEntry, SignableTransaction};
-        use kaspa_consensus_core::subnets::SubnetworkId;
-        use kaspa_consensus_core::hashing::{sighash::{calc_schnorr_signature_hash, calc_ecdsa_signature_hash}, sighash::SigHashReusedValuesUnsync, sighash_type::SigHashType};
-        use secp256k1::{Keypair, Message, Secp256k1, SecretKey};
-        if private_key_hex.is_null() { return ptr::null_mut(); }
-        let (is_testnet, fee_rate, change_addr_opt, utxos, outs, payload) = {
-                let g = __gens().lock().unwrap();
-                let Some(entry) = g.get(&gen) else { return ptr::null_mut(); };
-                (entry.is_testnet, entry.fee_rate, entry.change_address.clone(), entry.utxos.clone(), entry.outputs.clone(), entry.payload.clone())
-        };
-        let sig_type = match SigHashType::from_u8(sighash_type_u8) { Ok(t) => t, Err(_) => return ptr::null_mut() };
-        let use_ecdsa = match algo { 0 => false, 1 => true, _ => return ptr::null_mut() };
-        let default_rate: u64 = 1000;
-        let rate = if fee_rate <= 0 { default_rate } else { fee_rate as u64 };
-        let total_input: u64 = utxos.iter().map(|u| u.amount).sum();
-        let total_output_user: u64 = outs.iter().map(|o| o.amount).sum();
-        let sk_hex = unsafe { CStr::from_ptr(private_key_hex) }.to_string_lossy().to_string();
-        let sk_bytes = match hex::decode(sk_hex.trim()) { Ok(v) => v, Err(_) => return ptr::null_mut() };
-        if sk_bytes.len() != 32 { return ptr::null_mut(); }
-        let assumed_sig_script_len: usize = 66;
-        let build_tx = |include_change: bool, change_amount: u64| -> Option<(Transaction, Vec<UtxoEntry>, Vec<(String,u32)>, Vec<String>, Vec<Vec<u8>>)> {
-                let mut inputs: Vec<TransactionInput> = Vec::with_capacity(utxos.len());
-                let mut entries: Vec<UtxoEntry> = Vec::with_capacity(utxos.len());
-                let mut prev_outpoints: Vec<(String,u32)> = Vec::with_capacity(utxos.len());
-                let mut utxo_scripts_hex: Vec<String> = Vec::with_capacity(utxos.len());
-                let mut outputs_spk_bytes: Vec<Vec<u8>> = Vec::with_capacity(outs.len() + if include_change { 1 } else { 0 });
-                for u in utxos.iter() {
-                        let spk = ScriptPublicKey::new(0, u.spk_bytes.clone().into());
-                        let input = TransactionInput::new(TransactionOutpoint { transaction_id: u.txid.into(), index: u.index }, vec![], 0, 1);
-                        inputs.push(input);
-                        entries.push(UtxoEntry::new(u.amount, spk.clone(), 0, false));
-                        let txid_be_hex_str = hex::encode(u.txid);
-                        prev_outpoints.push((txid_be_hex_str, u.index));
-                        utxo_scripts_hex.push(hex::encode(&u.spk_bytes));
-                }
-                let mut outputs: Vec<TransactionOutput> = Vec::with_capacity(outs.len() + if include_change { 1 } else { 0 });
-                for o in outs.iter() {
-                        let addr = kaddr::Address::try_from(o.address.as_str()).ok()?;
-                        let expected_prefix = if is_testnet { kaddr::Prefix::Testnet } else { kaddr::Prefix::Mainnet };
-                        if addr.prefix != expected_prefix { return None; }
-                        let spk = pay_to_address_script(&addr);
-                        outputs_spk_bytes.push(spk.script().to_vec());
-                        outputs.push(TransactionOutput { value: o.amount, script_public_key: spk });
-                }
-                if include_change {
-                        let change_addr = kaddr::Address::try_from(change_addr_opt.as_ref()?.as_str()).ok()?;
-                        let spk = pay_to_address_script(&change_addr);
-                        outputs_spk_bytes.push(spk.script().to_vec());
-                        outputs.push(TransactionOutput { value: change_amount, script_public_key: spk });
+        let mut txid = [0u8;32]; txid.copy_from_slice(&bytes);
+        Ok(txid)
+}
+
+// The script hex may carry the 2-byte version prefix; when it does, it has to agree with `script_version`
+fn parse_utxo_fields(txid_be_hex: *const c_char, index: u32, amount: u64, script_pub_key_hex: *const c_char, script_version: u16) -> Result<TxGenUtxo, KisrError> {
+        let txid_str = c_str_arg(txid_be_hex, "txid_be_hex")?;
+        let spk_hex = c_str_arg(script_pub_key_hex, "script_pub_key_hex")?;
+        let txid_arr = parse_txid(&txid_str)?;
+        let spk_bytes = match decode_spk_hex_version_prefix(&spk_hex) {
+                Ok((Some(prefix), _)) if prefix != script_version => {
+                        return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("script public key version prefix {} disagrees with script version {}", prefix, script_version))
+                                .with("transactionId", txid_str.trim()).with("index", index).with("scriptVersion", script_version));
                 }
-                let mut tx = Transaction::new(0, inputs, outputs, 0, SubnetworkId::default(), 0, payload.clone());
-                for inp in tx.inputs.iter_mut() { inp.signature_script = vec![0u8; assumed_sig_script_len]; }
-                tx.finalize();
-                Some((tx, entries, prev_outpoints, utxo_scripts_hex, outputs_spk_bytes))
+                Ok((_, bytes)) => bytes,
+                Err(_) => return Err(KisrError::new(KisrErrorCode::InvalidHex, "invalid script public key hex").with("transactionId", txid_str.trim()).with("index", index)),
         };
-        let (tx0, entries0, prev0, spkhex0, outspk0) = match build_tx(false, 0) { Some(v) => v, None => return ptr::null_mut() };
-        let mass0 = {
-                use kaspa_consensus_core::config::params::Params;
-                use kaspa_consensus_core::network::NetworkType;
-                use kaspa_consensus_core::mass::MassCalculator;
-                let params: Params = NetworkType::Mainnet.into();
-                let mc = MassCalculator::new_with_consensus_params(&params);
-                let non = mc.calc_non_contextual_masses(&tx0);
-                let ctx = mc.calc_contextual_masses(&(&SignableTransaction::with_entries(tx0.clone(), entries0.clone())).as_verifiable()).unwrap_or(kaspa_consensus_core::mass::ContextualMasses::new(0));
-                ctx.max(non)
-        } as u64;
-        let min_fee0 = ((mass0 as u128) * (rate as u128) + 999) / 1000;
-        let change = (total_input as i128) - (total_output_user as i128) - (min_fee0 as i128);
-        let include_change = change_addr_opt.is_some() && change > 0;
-        let (mut tx, mut entries, mut prev_outpoints, mut utxo_scripts_hex, mut outputs_spk_bytes) = if include_change {
-                let change_u = change as u64;
-                match build_tx(true, change_u) { Some(v) => v, None => return ptr::null_mut() }
-        } else {
-                (tx0.clone(), entries0.clone(), prev0.clone(), spkhex0.clone(), outspk0.clone())
+        Ok(TxGenUtxo { script_version, ..TxGenUtxo::new(txid_arr, index, amount, spk_bytes) })
+}
+
+pub(crate) fn parse_utxo_entry(utxo_ptr: *const KaspaUtxoEntry) -> Result<TxGenUtxo, KisrError> {
+        if utxo_ptr.is_null() { return Err(KisrError::new(KisrErrorCode::NullArgument, "null utxo")); }
+        let utxo = unsafe { &*utxo_ptr };
+        parse_utxo_fields(utxo.txid_be_hex, utxo.index, utxo.amount, utxo.script_pub_key_hex, 0)
+}
+
+fn parse_utxo_entry_v2(utxo_ptr: *const KaspaUtxoEntryV2) -> Result<TxGenUtxo, KisrError> {
+        if utxo_ptr.is_null() { return Err(KisrError::new(KisrErrorCode::NullArgument, "null utxo")); }
+        let utxo = unsafe { &*utxo_ptr };
+        let u = parse_utxo_fields(utxo.txid_be_hex, utxo.index, utxo.amount, utxo.script_pub_key_hex, utxo.script_version)?;
+        Ok(TxGenUtxo { block_daa_score: utxo.block_daa_score, is_coinbase: utxo.is_coinbase, ..u })
+}
+
+// Accepts the flat SafeJSON-like shape or the RPC `{outpoint, utxoEntry}` shape returned by kaspa_rpc_get_utxos
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UtxoJson {
+        #[serde(rename_all = "camelCase")]
+        Flat {
+                transaction_id: String,
+                index: u32,
+                amount: serde_json::Value,
+                script_public_key: String,
+                #[serde(default)]
+                block_daa_score: Option<serde_json::Value>,
+                #[serde(default)]
+                is_coinbase: Option<bool>,
+                #[serde(default)]
+                candidate: bool,
+        },
+        #[serde(rename_all = "camelCase")]
+        Rpc {
+                outpoint: RpcOutpointJson,
+                utxo_entry: RpcUtxoEntryJson,
+                #[serde(default)]
+                candidate: bool,
+        },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcOutpointJson { transaction_id: String, index: u32 }
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RpcUtxoEntryJson {
+        amount: serde_json::Value,
+        script_public_key: String,
+        #[serde(default)]
+        block_daa_score: Option<serde_json::Value>,
+        #[serde(default)]
+        is_coinbase: Option<bool>,
+}
+
+fn json_u64(v: &serde_json::Value) -> Option<u64> {
+        match v {
+                serde_json::Value::String(s) => s.trim().parse::<u64>().ok(),
+                serde_json::Value::Number(n) => n.as_u64(),
+                _ => None,
+        }
+}
+
+fn parse_utxo_json(u: UtxoJson) -> Result<(TxGenUtxo, bool), KisrError> {
+        let (txid_hex, index, amount, spk_hex, daa, is_coinbase, candidate) = match u {
+                UtxoJson::Flat { transaction_id, index, amount, script_public_key, block_daa_score, is_coinbase, candidate } =>
+                        (transaction_id, index, amount, script_public_key, block_daa_score, is_coinbase, candidate),
+                UtxoJson::Rpc { outpoint, utxo_entry, candidate } =>
+                        (outpoint.transaction_id, outpoint.index, utxo_entry.amount, utxo_entry.script_public_key, utxo_entry.block_daa_score, utxo_entry.is_coinbase, candidate),
         };
-        if include_change {
-                let mass1 = {
-                        use kaspa_consensus_core::config::params::Params;
-                        use kaspa_consensus_core::network::NetworkType;
-                        use kaspa_consensus_core::mass::MassCalculator;
-                        let params: Params = NetworkType::Mainnet.into();
-                        let mc = MassCalculator::new_with_consensus_params(&params);
-                        let non = mc.calc_non_contextual_masses(&tx);
-                        let ctx = mc.calc_contextual_masses(&(&SignableTransaction::with_entries(tx.clone(), entries.clone())).as_verifiable()).unwrap_or(kaspa_consensus_core::mass::ContextualMasses::new(0));
-                        ctx.max(non)
-                } as u64;
-                let min_fee1 = ((mass1 as u128) * (rate as u128) + 999) / 1000;
-                let change1 = (total_input as i128) - (total_output_user as i128) - (min_fee1 as i128);
-                if change1 <= 0 {
-                        // Fallback to no-change tx
-                        tx = tx0;
-                        entries = entries0;
-                        prev_outpoints = prev0;
-                        utxo_scripts_hex = spkhex0;
-                        outputs_spk_bytes = outspk0;
-                } else {
-                        if let Some(last) = tx.outputs.last_mut() { last.value = change1 as u64; }
-                }
+        let invalid = |field: &str| KisrError::new(KisrErrorCode::InvalidArgument, format!("invalid {} for {}:{}", field, txid_hex, index))
+                .with("transactionId", txid_hex.as_str()).with("index", index).with("field", field);
+        let txid = parse_txid(&txid_hex)?;
+        let amount = json_u64(&amount).ok_or_else(|| invalid("amount"))?;
+        let (script_version, spk_bytes) = decode_versioned_spk_hex(&spk_hex).map_err(|_| invalid("scriptPublicKey"))?;
+        // Both decide coinbase maturity; a missing value must not pass as a mature, non-coinbase UTXO
+        let block_daa_score = daa.as_ref().and_then(json_u64).ok_or_else(|| invalid("blockDaaScore"))?;
+        let is_coinbase = is_coinbase.ok_or_else(|| invalid("isCoinbase"))?;
+        Ok((TxGenUtxo { txid, index, amount, spk_bytes, script_version, block_daa_score, is_coinbase }, candidate))
+}
+
+pub(crate) fn tx_generator_add_utxo(handle: c_int, utxo_ptr: *const KaspaUtxoEntry) -> Result<(), KisrError> {
+        let u = parse_utxo_entry(utxo_ptr)?;
+        with_gen(handle, |gen| { gen.add_utxo(u); Ok(()) })
+}
+
+pub(crate) fn tx_generator_add_utxo_v2(handle: c_int, utxo_ptr: *const KaspaUtxoEntryV2) -> Result<(), KisrError> {
+        let u = parse_utxo_entry_v2(utxo_ptr)?;
+        with_gen(handle, |gen| { gen.add_utxo(u); Ok(()) })
+}
+
+// Adds every UTXO in the array, or none of them. Entries with `"candidate": true` go to the selection pool.
+pub(crate) fn tx_generator_add_utxos_json(handle: c_int, utxos_json: *const c_char) -> Result<(), KisrError> {
+        let s = c_str_arg(utxos_json, "utxos_json")?;
+        let items: Vec<UtxoJson> = serde_json::from_str(&s).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid json: {}", e)))?;
+        let mut parsed = Vec::with_capacity(items.len());
+        for (i, item) in items.into_iter().enumerate() {
+                parsed.push(parse_utxo_json(item).map_err(|e| e.with("position", i))?);
         }
-        let secp = Secp256k1::new();
-        let signable = SignableTransaction::with_entries(tx.clone(), entries.clone());
-        let mut reused = SigHashReusedValuesUnsync::new();
-        if use_ecdsa {
-                let sk = match SecretKey::from_slice(&sk_bytes) { Ok(k) => k, Err(_) => return ptr::null_mut() };
-                for input_index in 0..signable.tx.inputs.len() {
-                        let sig_hash = calc_ecdsa_signature_hash(&signable.as_verifiable(), input_index, sig_type, &mut reused);
-                        let msg = match Message::from_digest_slice(&sig_hash.as_bytes()) { Ok(m) => m, Err(_) => return ptr::null_mut() };
-                        let sig = secp.sign_ecdsa(&msg, &sk);
-                        let mut der = sig.serialize_der().to_vec();
-                        der.push(sig_type.to_u8());
-                        let mut sig_script: Vec<u8> = Vec::with_capacity(der.len() + 5);
-                        crate::script_push_data(&mut sig_script, &der);
-                        tx.inputs[input_index].signature_script = sig_script;
+        with_gen(handle, |gen| {
+                for (u, candidate) in parsed.into_iter() {
+                        if candidate { gen.add_candidate_utxo(u); } else { gen.add_utxo(u); }
                 }
+                Ok(())
+        })
+}
+
+pub(crate) fn tx_generator_set_virtual_daa_score(handle: c_int, virtual_daa_score: u64) -> Result<(), KisrError> {
+        with_gen(handle, |gen| { gen.virtual_daa_score(virtual_daa_score); Ok(()) })
+}
+
+pub(crate) fn tx_generator_add_candidate_utxo(handle: c_int, utxo_ptr: *const KaspaUtxoEntry) -> Result<(), KisrError> {
+        let u = parse_utxo_entry(utxo_ptr)?;
+        with_gen(handle, |gen| { gen.add_candidate_utxo(u); Ok(()) })
+}
+
+pub(crate) fn tx_generator_set_selection_strategy(handle: c_int, strategy: c_int) -> Result<(), KisrError> {
+        let strategy = SelectionStrategy::from_code(strategy)
+                .ok_or_else(|| KisrError::new(KisrErrorCode::InvalidArgument, format!("unknown selection strategy {}", strategy)).with("strategy", strategy))?;
+        with_gen(handle, |gen| { gen.selection_strategy(strategy); Ok(()) })
+}
+
+pub(crate) fn tx_generator_exclude_outpoint(handle: c_int, txid_hex: *const c_char, index: u32) -> Result<(), KisrError> {
+        let (txid, index) = parse_outpoint(txid_hex, index)?;
+        with_gen(handle, |gen| { gen.exclude_outpoint(txid, index); Ok(()) })
+}
+
+pub(crate) fn tx_generator_set_input_sighash(handle: c_int, input_index: u32, sighash_type: u8) -> Result<(), KisrError> {
+        with_gen(handle, |gen| gen.input_sighash(input_index, sighash_type).map(|_| ()))
+}
+
+// `indices_json` is a JSON array of input indices; null signs every input again
+pub(crate) fn tx_generator_set_sign_indices(handle: c_int, indices_json: *const c_char) -> Result<(), KisrError> {
+        let indices = if indices_json.is_null() {
+                None
         } else {
-                let keypair = match Keypair::from_seckey_slice(&secp, &sk_bytes) { Ok(k) => k, Err(_) => return ptr::null_mut() };
-                for input_index in 0..signable.tx.inputs.len() {
-                        let sig_hash = calc_schnorr_signature_hash(&signable.as_verifiable(), input_index, sig_type, &mut reused);
-                        let msg = match Message::from_digest_slice(&sig_hash.as_bytes()) { Ok(m) => m, Err(_) => return ptr::null_mut() };
-                        let aux = [0u8;32];
-                        let sig = secp.sign_schnorr_with_aux_rand(&msg, &keypair, &aux);
-                        let sig_bytes: [u8;64] = sig.as_ref().clone();
-                        let mut sig_script = Vec::with_capacity(1 + 64 + 1);
-                        sig_script.push(64u8 + 1u8);
-                        sig_script.extend_from_slice(&sig_bytes);
-                        sig_script.push(sig_type.to_u8());
-                        tx.inputs[input_index].signature_script = sig_script;
-                }
-        }
-        tx.finalize();
-        let id = tx.id().to_string();
-        let mass = {
-                use kaspa_consensus_core::config::params::Params;
-                use kaspa_consensus_core::network::NetworkType;
-                use kaspa_consensus_core::mass::{MassCalculator};
-                let params: Params = NetworkType::Mainnet.into();
-                let mc = MassCalculator::new_with_consensus_params(&params);
-                let non = mc.calc_non_contextual_masses(&tx);
-                let ctx = mc.calc_contextual_masses(&(&SignableTransaction::with_entries(tx.clone(), entries.clone())).as_verifiable()).unwrap_or(kaspa_consensus_core::mass::ContextualMasses::new(0));
-                ctx.max(non)
+                let s = unsafe { CStr::from_ptr(indices_json) }.to_string_lossy().to_string();
+                let v = serde_json::from_str::<Vec<u32>>(&s).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid json: {}", e)))?;
+                Some(v.into_iter().collect::<HashSet<u32>>())
         };
-        let mut inputs_json: Vec<SafeJsonInput> = Vec::with_capacity(tx.inputs.len());
-        for (i, inp) in tx.inputs.iter().enumerate() {
-                let (txid_be_hex, index) = &prev_outpoints[i];
-                let utxo_spk_prefixed = format!("0000{}", utxo_scripts_hex[i].to_lowercase());
-                let utxo_amount_str = utxos[i].amount.to_string();
-                let utxo_block_daa_score_str = "0".to_string();
-                let utxo_is_coinbase = false;
-                inputs_json.push(SafeJsonInput {
-                        transaction_id: txid_be_hex.clone(),
-                        index: *index,
-                        signature_script: hex::encode(&inp.signature_script),
-                        sequence: inp.sequence.to_string(),
-                        sig_op_count: 1,
-                        utxo: SafeJsonInputUtxo {
-                                address: None,
-                                amount: utxo_amount_str,
-                                script_public_key: utxo_spk_prefixed,
-                                block_daa_score: utxo_block_daa_score_str,
-                                is_coinbase: utxo_is_coinbase,
-                        },
-                });
-        }
-        let mut outputs_json: Vec<SafeJsonOutput> = Vec::with_capacity(tx.outputs.len());
-        for (idx, out) in tx.outputs.iter().enumerate() {
-                let spk_hex = hex::encode(&outputs_spk_bytes[idx].as_slice());
-                let spk_prefixed = format!("0000{}", spk_hex);
-                outputs_json.push(SafeJsonOutput { value: out.value.to_string(), script_public_key: spk_prefixed });
-        }
-        let safe = SafeJsonTx {
-                id,
-                inputs: inputs_json,
-                outputs: outputs_json,
-                version: tx.version,
-                lock_time: tx.lock_time.to_string(),
-                gas: tx.gas.to_string(),
-                subnetwork_id: format!("{:040}", 0),
-                payload: hex::encode(&payload),
-                mass: mass.to_string(),
-        };
-        match serde_json::to_string(&safe) { Ok(s) => CString::new(s).ok().map(CString::into_raw).unwrap_or(ptr::null_mut()), Err(_) => ptr::null_mut() }
+        with_gen(handle, |gen| { gen.sign_indices(indices); Ok(()) })
+}
+
+fn outpoints_json(utxos: &[TxGenUtxo]) -> String {
+        let outpoints: Vec<serde_json::Value> = utxos.iter().map(|u| serde_json::json!({
+                "transactionId": hex::encode(u.txid),
+                "index": u.index,
+                "amount": u.amount.to_string(),
+        })).collect();
+        serde_json::Value::Array(outpoints).to_string()
+}
+
+pub(crate) fn tx_generator_reserve_selection(gen: c_int) -> Result<String, KisrError> {
+        with_gen(gen, |gen| Ok(outpoints_json(&gen.reserve_selection()?)))
+}
+
+pub(crate) fn tx_generator_selected_outpoints(gen: c_int) -> Result<String, KisrError> {
+        Ok(outpoints_json(&gen_snapshot(gen)?.selected_utxos()?))
+}
+
+pub(crate) fn tx_generator_add_output(handle: c_int, output_ptr: *const KaspaOutputEntry) -> Result<(), KisrError> {
+        if output_ptr.is_null() { return Err(KisrError::new(KisrErrorCode::NullArgument, "null output")); }
+        let output = unsafe { &*output_ptr };
+        let addr_str = c_str_arg(output.address, "address")?;
+        with_gen(handle, |gen| gen.add_output(&addr_str, output.amount).map(|_| ()))
+}
+
+pub(crate) fn tx_generator_build_unsigned_safejson(gen: c_int) -> Result<String, KisrError> {
+        to_json_string(&gen_snapshot(gen)?.build_unsigned()?)
+}
+
+pub(crate) fn build_unsigned(entry: &TxGenEntry) -> Result<SafeJsonTx, KisrError> {
+        Ok(TxPlan::new(entry)?.unsigned())
+}
+
+pub(crate) fn tx_generator_build_and_sign_safejson_with_type_and_algo(gen: c_int, private_key_hex: *const c_char, sighash_type_u8: u8, algo: u8) -> Result<String, KisrError> {
+        let sk_hex = c_str_arg(private_key_hex, "private_key_hex")?;
+        to_json_string(&gen_snapshot(gen)?.build_and_sign(&sk_hex, sighash_type_u8, algo)?)
+}
+
+pub(crate) fn tx_generator_build_and_sign_safejson_with_keys(gen: c_int, private_keys_json: *const c_char, sighash_type_u8: u8) -> Result<String, KisrError> {
+        let keys_str = c_str_arg(private_keys_json, "private_keys_json")?;
+        let gen = gen_snapshot(gen)?;
+        let keys = serde_json::from_str::<Vec<String>>(&keys_str).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid keys json: {}", e)))?;
+        to_json_string(&gen.build_and_sign_with_keys(&keys, sighash_type_u8)?)
+}
+
+// For the in-crate invite flows, which build entries without going through a handle
+pub(crate) fn build_and_sign_entry(entry: &TxGenEntry, private_key_hex: &str, sighash_type_u8: u8, algo: u8) -> Result<SafeJsonTx, KisrError> {
+        let signer = InputSigner::single(private_key_hex, algo)?;
+        build_and_sign_with(entry, &signer, sighash_type_u8)
+}
+
+pub(crate) fn build_and_sign_with(entry: &TxGenEntry, signer: &InputSigner, sighash_type_u8: u8) -> Result<SafeJsonTx, KisrError> {
+        let plan = TxPlan::new(entry)?;
+        plan.sign(signer, &plan.sign_plan(sighash_type_u8)?)
 }
 
 // C ABI wrappers expected by callers
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_new(is_testnet: bool) -> c_int {
-        tx_generator_new(is_testnet)
+        ffi_guard("kaspa_tx_generator_new", || {
+                tx_generator_new(is_testnet)
+        })
+}
+
+// `network` is "mainnet", "testnet-10", "testnet-11", "simnet", "devnet" (or any NetworkId string). Returns a negative
+// KisrErrorCode (invalidNetwork) if unknown.
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_new_with_network(network: *const c_char) -> c_int {
+        ffi_guard("kaspa_tx_generator_new_with_network", || {
+                tx_generator_new_with_network(network).unwrap_or_else(|e| fail("kaspa_tx_generator_new_with_network", e))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_free(handle: c_int) -> c_int {
-        tx_generator_free(handle)
+        ffi_guard("kaspa_tx_generator_free", || {
+                ffi_status("kaspa_tx_generator_free", tx_generator_free(handle))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_clear(handle: c_int) -> c_int {
-        tx_generator_clear(handle)
+        ffi_guard("kaspa_tx_generator_clear", || {
+                ffi_status("kaspa_tx_generator_clear", tx_generator_clear(handle))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_set_change_address(handle: c_int, address: *const c_char) -> c_int {
-        tx_generator_set_change_address(handle, address)
+        ffi_guard("kaspa_tx_generator_set_change_address", || {
+                ffi_status("kaspa_tx_generator_set_change_address", tx_generator_set_change_address(handle, address))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_set_fee_rate(handle: c_int, fee_rate_sompi_per_kilomass: i64) -> c_int {
-        tx_generator_set_fee_rate(handle, fee_rate_sompi_per_kilomass)
+        ffi_guard("kaspa_tx_generator_set_fee_rate", || {
+                ffi_status("kaspa_tx_generator_set_fee_rate", tx_generator_set_fee_rate(handle, fee_rate_sompi_per_kilomass))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_add_utxo(handle: c_int, utxo_ptr: *const KaspaUtxoEntry) -> c_int {
-        tx_generator_add_utxo(handle, utxo_ptr)
+        ffi_guard("kaspa_tx_generator_add_utxo", || {
+                ffi_status("kaspa_tx_generator_add_utxo", tx_generator_add_utxo(handle, utxo_ptr))
+        })
+}
+
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_add_utxo_v2(handle: c_int, utxo_ptr: *const KaspaUtxoEntryV2) -> c_int {
+        ffi_guard("kaspa_tx_generator_add_utxo_v2", || {
+                ffi_status("kaspa_tx_generator_add_utxo_v2", tx_generator_add_utxo_v2(handle, utxo_ptr))
+        })
+}
+
+// JSON array of `{transactionId, index, amount, scriptPublicKey, blockDaaScore, isCoinbase, candidate?}`
+// (or kaspa_rpc_get_utxos entries); scriptPublicKey carries the 4-hex-digit version prefix
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_add_utxos_json(handle: c_int, utxos_json: *const c_char) -> c_int {
+        ffi_guard("kaspa_tx_generator_add_utxos_json", || {
+                ffi_status("kaspa_tx_generator_add_utxos_json", tx_generator_add_utxos_json(handle, utxos_json))
+        })
+}
+
+// Coinbase UTXOs are only spent once this score is past their maturity
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_set_virtual_daa_score(handle: c_int, virtual_daa_score: u64) -> c_int {
+        ffi_guard("kaspa_tx_generator_set_virtual_daa_score", || {
+                ffi_status("kaspa_tx_generator_set_virtual_daa_score", tx_generator_set_virtual_daa_score(handle, virtual_daa_score))
+        })
+}
+
+// Adds a UTXO the selection strategy may spend; see kaspa_tx_generator_set_selection_strategy
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_add_candidate_utxo(handle: c_int, utxo_ptr: *const KaspaUtxoEntry) -> c_int {
+        ffi_guard("kaspa_tx_generator_add_candidate_utxo", || {
+                ffi_status("kaspa_tx_generator_add_candidate_utxo", tx_generator_add_candidate_utxo(handle, utxo_ptr))
+        })
+}
+
+// KASPA_SELECT_*: 0 none (spend only added UTXOs), 1 closest single, 2 largest-first, 3 smallest-first, 4 branch-and-bound
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_set_selection_strategy(handle: c_int, strategy: c_int) -> c_int {
+        ffi_guard("kaspa_tx_generator_set_selection_strategy", || {
+                ffi_status("kaspa_tx_generator_set_selection_strategy", tx_generator_set_selection_strategy(handle, strategy))
+        })
+}
+
+// JSON array of `{transactionId, index, amount}`: the outpoints the next build will spend
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_selected_outpoints(gen: c_int) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_selected_outpoints", || {
+                ffi_string("kaspa_tx_generator_selected_outpoints", tx_generator_selected_outpoints(gen))
+        })
+}
+
+// Never spend this outpoint from this handle, whether added directly or as a candidate
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_exclude_outpoint(handle: c_int, txid_hex: *const c_char, index: u32) -> c_int {
+        ffi_guard("kaspa_tx_generator_exclude_outpoint", || {
+                ffi_status("kaspa_tx_generator_exclude_outpoint", tx_generator_exclude_outpoint(handle, txid_hex, index))
+        })
+}
+
+// Same JSON as kaspa_tx_generator_selected_outpoints; release with kaspa_outpoint_release
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_reserve_selection(gen: c_int) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_reserve_selection", || {
+                ffi_string("kaspa_tx_generator_reserve_selection", tx_generator_reserve_selection(gen))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_add_output(handle: c_int, output_ptr: *const KaspaOutputEntry) -> c_int {
-        tx_generator_add_output(handle, output_ptr)
+        ffi_guard("kaspa_tx_generator_add_output", || {
+                ffi_status("kaspa_tx_generator_add_output", tx_generator_add_output(handle, output_ptr))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_set_payload_hex(handle: c_int, payload_hex: *const c_char) -> c_int {
-        tx_generator_set_payload_hex(handle, payload_hex)
+        ffi_guard("kaspa_tx_generator_set_payload_hex", || {
+                ffi_status("kaspa_tx_generator_set_payload_hex", tx_generator_set_payload_hex(handle, payload_hex))
+        })
 }
 
 #[no_mangle]
 pub extern "C" fn kaspa_tx_generator_build_unsigned_safejson(gen: c_int) -> *mut c_char {
-        tx_generator_build_unsigned_safejson(gen)
+        ffi_guard("kaspa_tx_generator_build_unsigned_safejson", || {
+                ffi_string("kaspa_tx_generator_build_unsigned_safejson", tx_generator_build_unsigned_safejson(gen))
+        })
 }
 
 #[no_mangle]
@@ -494,5 +556,74 @@ pub extern "C" fn kaspa_tx_generator_build_and_sign_safejson_with_type_and_algo(
         sighash_type: u8,
         algo: u8,
 ) -> *mut c_char {
-        tx_generator_build_and_sign_safejson_with_type_and_algo(gen, private_key_hex, sighash_type, algo)
+        ffi_guard("kaspa_tx_generator_build_and_sign_safejson_with_type_and_algo", || {
+                ffi_string("kaspa_tx_generator_build_and_sign_safejson_with_type_and_algo", tx_generator_build_and_sign_safejson_with_type_and_algo(gen, private_key_hex, sighash_type, algo))
+        })
+}
+
+// Overrides the build call's sighash type for one input, e.g. 0x82 (None|AnyoneCanPay) on a KISRUTXO.
+// Fails with invalidSighashType for an unknown sighash type.
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_set_input_sighash(handle: c_int, input_index: u32, sighash_type: u8) -> c_int {
+        ffi_guard("kaspa_tx_generator_set_input_sighash", || {
+                ffi_status("kaspa_tx_generator_set_input_sighash", tx_generator_set_input_sighash(handle, input_index, sighash_type))
+        })
+}
+
+// Restricts signing to the given input indices; the rest are returned with empty signature scripts
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_set_sign_indices(handle: c_int, indices_json: *const c_char) -> c_int {
+        ffi_guard("kaspa_tx_generator_set_sign_indices", || {
+                ffi_status("kaspa_tx_generator_set_sign_indices", tx_generator_set_sign_indices(handle, indices_json))
+        })
+}
+
+// `private_keys_json` is a JSON array of hex private keys. Each input is signed by the key whose P2PK script
+// (Schnorr or ECDSA) matches its UTXO, so the algorithm follows the address type.
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_build_and_sign_safejson_with_keys(gen: c_int, private_keys_json: *const c_char, sighash_type: u8) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_build_and_sign_safejson_with_keys", || {
+                ffi_string("kaspa_tx_generator_build_and_sign_safejson_with_keys", tx_generator_build_and_sign_safejson_with_keys(gen, private_keys_json, sighash_type))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+        use std::ffi::CString;
+
+        const TXID: &str = "b1682dd4b211409c27f3de6f4865212b8d0eacc6f2da79d17a8ae529d4329fbf";
+        const SPK: &str = "20c5e0d1bd2e9e8a4bfe2a4c9e4d5b8b6f1b2e7f7c8d7a5c4f3b2a1908070605ac";
+
+        fn json_utxo(extra: serde_json::Value) -> Result<(TxGenUtxo, bool), KisrError> {
+                let mut v = serde_json::json!({ "transactionId": TXID, "index": 1, "amount": "1000", "scriptPublicKey": format!("0000{}", SPK) });
+                v.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+                parse_utxo_json(serde_json::from_value(v).unwrap())
+        }
+
+        #[test]
+        fn json_utxos_must_state_daa_score_and_coinbase() {
+                let (u, candidate) = json_utxo(serde_json::json!({ "blockDaaScore": "77", "isCoinbase": true, "candidate": true })).unwrap();
+                assert_eq!((u.block_daa_score, u.is_coinbase, candidate), (77, true, true));
+                for (extra, field) in [(serde_json::json!({ "isCoinbase": false }), "blockDaaScore"), (serde_json::json!({ "blockDaaScore": 5 }), "isCoinbase"), (serde_json::json!({ "blockDaaScore": "x", "isCoinbase": false }), "blockDaaScore")] {
+                        let err = json_utxo(extra).unwrap_err();
+                        assert_eq!(err.code, KisrErrorCode::InvalidArgument);
+                        assert_eq!(err.context["field"], field);
+                }
+        }
+
+        #[test]
+        fn version_prefix_must_match_script_version() {
+                let txid = CString::new(TXID).unwrap();
+                let entry = |spk: &str, script_version: u16| {
+                        let spk = CString::new(spk).unwrap();
+                        parse_utxo_entry_v2(&KaspaUtxoEntryV2 { txid_be_hex: txid.as_ptr(), index: 0, amount: 1, script_pub_key_hex: spk.as_ptr(), script_version, block_daa_score: 9, is_coinbase: false })
+                };
+                let bare = entry(SPK, 1).unwrap();
+                assert_eq!((bare.spk_bytes, bare.script_version), (hex::decode(SPK).unwrap(), 1));
+                assert_eq!(entry(&format!("0001{}", SPK), 1).unwrap().spk_bytes, hex::decode(SPK).unwrap());
+                assert_eq!(entry(&format!("0001{}", SPK), 0).unwrap_err().code, KisrErrorCode::InvalidArgument);
+                assert_eq!(entry(&format!("0000{}", SPK), 1).unwrap_err().code, KisrErrorCode::InvalidArgument);
+                assert_eq!(entry("zz", 0).unwrap_err().code, KisrErrorCode::InvalidHex);
+        }
 }
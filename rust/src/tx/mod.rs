@@ -1 +1,9 @@
 pub mod generator;
+pub mod plan;
+pub mod select;
+pub mod reserve;
+pub mod cancel;
+pub mod chain;
+pub mod sign;
+pub mod pskt;
+pub mod external;
@@ -0,0 +1,214 @@
+use kaspa_addresses as kaddr;
+use kaspa_txscript::pay_to_address_script;
+
+use kaspa_consensus_core::mass::MassCalculator;
+use kaspa_consensus_core::subnets::SubnetworkId;
+use kaspa_consensus_core::tx::{ScriptPublicKey, Transaction, TransactionInput, TransactionOutpoint, TransactionOutput, UtxoEntry};
+
+use crate::{SafeJsonInput, SafeJsonInputUtxo, SafeJsonOutput, SafeJsonTx};
+use crate::error::{KisrError, KisrErrorCode};
+use crate::fee::{calc_tx_masses, min_fee_for_mass, TxMasses, MAX_STANDARD_TX_MASS};
+use super::generator::TxGenEntry;
+use super::select::resolve_inputs;
+use super::sign::{p2pk_kind, sign_inputs, InputSigner, SignPlan};
+
+// Every build goes through one plan: inputs resolved, change settled and the transaction assembled with
+// placeholder signature scripts. Unsigned SafeJSON, PSKTs, sighash export and signing are stages on top of it,
+// so an unsigned estimate always describes exactly the transaction that later gets signed.
+
+// P2PK signature scripts: one push of the signature plus its sighash byte. Schnorr signatures are 64 bytes,
+// DER-encoded ECDSA ones up to 72.
+const SCHNORR_SIG_SCRIPT_LEN: usize = 1 + 64 + 1;
+const ECDSA_SIG_SCRIPT_LEN: usize = 1 + 72 + 1;
+const MAX_CHANGE_ITERATIONS: usize = 8;
+
+pub(crate) fn assemble_failed() -> KisrError {
+        KisrError::new(KisrErrorCode::BuildFailed, "failed to assemble transaction")
+}
+
+// Sized from the UTXO's P2PK kind so the mass is never below the signed transaction's; other scripts are
+// assumed to be spent with a Schnorr-sized signature
+pub(crate) fn placeholder_sig_script(spk_bytes: &[u8]) -> Vec<u8> {
+        match p2pk_kind(spk_bytes) {
+                Some(true) => vec![0u8; ECDSA_SIG_SCRIPT_LEN],
+                _ => vec![0u8; SCHNORR_SIG_SCRIPT_LEN],
+        }
+}
+
+// Inputs carry placeholder signature scripts so the mass matches the signed transaction
+pub(crate) fn assemble_tx(entry: &TxGenEntry, change: Option<u64>) -> Option<(Transaction, Vec<UtxoEntry>)> {
+        let mut inputs: Vec<TransactionInput> = Vec::with_capacity(entry.utxos.len());
+        let mut entries: Vec<UtxoEntry> = Vec::with_capacity(entry.utxos.len());
+        for u in entry.utxos.iter() {
+                let spk = ScriptPublicKey::new(u.script_version, u.spk_bytes.clone().into());
+                inputs.push(TransactionInput::new(TransactionOutpoint { transaction_id: u.txid.into(), index: u.index }, placeholder_sig_script(&u.spk_bytes), 0, 1));
+                entries.push(UtxoEntry::new(u.amount, spk, u.block_daa_score, u.is_coinbase));
+        }
+        let mut outputs: Vec<TransactionOutput> = Vec::with_capacity(entry.outputs.len() + 1);
+        for o in entry.outputs.iter() {
+                let addr = kaddr::Address::try_from(o.address.as_str()).ok()?;
+                if addr.prefix != entry.prefix() { return None; }
+                outputs.push(TransactionOutput { value: o.amount, script_public_key: pay_to_address_script(&addr) });
+        }
+        if let Some(change_amount) = change {
+                let change_addr = kaddr::Address::try_from(entry.change_address.as_ref()?.as_str()).ok()?;
+                outputs.push(TransactionOutput { value: change_amount, script_public_key: pay_to_address_script(&change_addr) });
+        }
+        let mut tx = Transaction::new(0, inputs, outputs, 0, SubnetworkId::default(), 0, entry.payload.clone());
+        tx.finalize();
+        Some((tx, entries))
+}
+
+// Picks the change amount, or None to leave the excess to the fee. KIP-9 storage mass grows as outputs shrink,
+// so the change is re-sized against the mass of the transaction that carries it. The change only ever shrinks,
+// which makes the iteration converge on the largest change whose own transaction still pays its fee. Change that
+// costs at least as much fee as it returns, or that pushes the tx past the standard mass limit, is dropped.
+// Splitting the change is never an alternative: storage mass charges C/o per output, and k outputs of c/k
+// cost k² times what one output of c does, so the only lighter transaction is the one without it.
+// Fails when even the change-less transaction cannot be built or is non-standard.
+pub(crate) fn settle_change<F>(mc: &MassCalculator, rate: u64, total_input: u64, total_output: u64, has_change_address: bool, build: F) -> Result<Option<u64>, KisrError>
+where F: Fn(Option<u64>) -> Option<(Transaction, Vec<UtxoEntry>)> {
+        let (tx0, entries0) = build(None).ok_or_else(assemble_failed)?;
+        let mass0 = calc_tx_masses(mc, &tx0, &entries0).max();
+        if mass0 > MAX_STANDARD_TX_MASS {
+                return Err(KisrError::new(KisrErrorCode::MassLimitExceeded, format!("transaction mass {} exceeds the standard limit {}", mass0, MAX_STANDARD_TX_MASS))
+                        .with("mass", mass0).with("maxMass", MAX_STANDARD_TX_MASS).with("inputCount", tx0.inputs.len()));
+        }
+        let fee0 = min_fee_for_mass(mass0, rate);
+        if !has_change_address || total_input <= total_output.saturating_add(fee0) { return Ok(None); }
+        let mut change = total_input - total_output - fee0;
+        for _ in 0..MAX_CHANGE_ITERATIONS {
+                let (tx1, entries1) = build(Some(change)).ok_or_else(assemble_failed)?;
+                let mass1 = calc_tx_masses(mc, &tx1, &entries1).max();
+                if mass1 > MAX_STANDARD_TX_MASS { return Ok(None); }
+                let fee1 = min_fee_for_mass(mass1, rate);
+                if total_input <= total_output.saturating_add(fee1) { return Ok(None); }
+                let affordable = total_input - total_output - fee1;
+                if fee1.saturating_sub(fee0) >= affordable { return Ok(None); }
+                // Fixed point: the transaction carrying this change leaves at least its own fee
+                if affordable >= change { return Ok(Some(change)); }
+                change = affordable;
+        }
+        // Still shrinking: the change is being eaten by its own storage mass
+        Ok(None)
+}
+
+// SafeJSON for a transaction whose UTXO entries are known. `masses` is passed in because unsigned and partially
+// signed transactions report the mass of the fully signed one.
+pub(crate) fn safe_json_from_tx(tx: &Transaction, entries: &[UtxoEntry], masses: &TxMasses) -> SafeJsonTx {
+        let inputs = tx.inputs.iter().zip(entries.iter()).map(|(inp, e)| SafeJsonInput {
+                transaction_id: inp.previous_outpoint.transaction_id.to_string(),
+                index: inp.previous_outpoint.index,
+                signature_script: hex::encode(&inp.signature_script),
+                sequence: inp.sequence.to_string(),
+                sig_op_count: inp.sig_op_count,
+                utxo: SafeJsonInputUtxo {
+                        address: None,
+                        amount: e.amount.to_string(),
+                        script_public_key: format!("{:04x}{}", e.script_public_key.version(), hex::encode(e.script_public_key.script())),
+                        block_daa_score: e.block_daa_score.to_string(),
+                        is_coinbase: e.is_coinbase,
+                },
+        }).collect();
+        let outputs = tx.outputs.iter().map(|o| SafeJsonOutput {
+                value: o.value.to_string(),
+                script_public_key: format!("{:04x}{}", o.script_public_key.version(), hex::encode(o.script_public_key.script())),
+        }).collect();
+        SafeJsonTx {
+                id: tx.id().to_string(),
+                inputs,
+                outputs,
+                version: tx.version,
+                lock_time: tx.lock_time.to_string(),
+                gas: tx.gas.to_string(),
+                subnetwork_id: format!("{:040}", 0),
+                payload: hex::encode(&tx.payload),
+                mass: masses.max().to_string(),
+                compute_mass: masses.compute.to_string(),
+                storage_mass: masses.storage.to_string(),
+        }
+}
+
+pub(crate) struct TxPlan {
+        // With the selection strategy applied: `utxos` are exactly the inputs, in order
+        pub(crate) entry: TxGenEntry,
+        pub(crate) mc: MassCalculator,
+        // Every input holds a placeholder signature script
+        pub(crate) tx: Transaction,
+        pub(crate) entries: Vec<UtxoEntry>,
+}
+
+impl TxPlan {
+        pub(crate) fn new(entry: &TxGenEntry) -> Result<Self, KisrError> {
+                let entry = resolve_inputs(entry)?;
+                let mc = MassCalculator::new_with_consensus_params(&entry.params());
+                let total_input: u64 = entry.utxos.iter().map(|u| u.amount).sum();
+                let total_output: u64 = entry.outputs.iter().map(|o| o.amount).sum();
+                let change = settle_change(&mc, entry.rate(), total_input, total_output, entry.change_address.is_some(), |c| assemble_tx(&entry, c))?;
+                let (tx, entries) = assemble_tx(&entry, change).ok_or_else(assemble_failed)?;
+                Ok(TxPlan { entry, mc, tx, entries })
+        }
+
+        // The mass of the fully signed transaction, which is what the fee was settled against
+        pub(crate) fn masses(&self) -> TxMasses { calc_tx_masses(&self.mc, &self.tx, &self.entries) }
+
+        // Sighash types and the inputs to sign, from the generator's per-input settings
+        pub(crate) fn sign_plan(&self, sighash_type_u8: u8) -> Result<SignPlan, KisrError> {
+                SignPlan::new(sighash_type_u8, &self.entry.input_sighash, self.entry.sign_indices.as_ref(), self.tx.inputs.len())
+        }
+
+        pub(crate) fn unsigned(&self) -> SafeJsonTx {
+                self.finish(self.tx.clone(), |_| false, &self.masses())
+        }
+
+        // Inputs the sign plan leaves out are returned with empty signature scripts
+        pub(crate) fn sign(&self, signer: &InputSigner, plan: &SignPlan) -> Result<SafeJsonTx, KisrError> {
+                let mut tx = self.tx.clone();
+                sign_inputs(&mut tx, &self.entries, signer, plan)?;
+                // Signed inputs at their real signature scripts, the others still at their placeholders
+                let masses = calc_tx_masses(&self.mc, &tx, &self.entries);
+                Ok(self.finish(tx, |i| plan.signs(i), &masses))
+        }
+
+        fn finish(&self, mut tx: Transaction, signed: impl Fn(usize) -> bool, masses: &TxMasses) -> SafeJsonTx {
+                for (i, inp) in tx.inputs.iter_mut().enumerate() {
+                        if !signed(i) { inp.signature_script.clear(); }
+                }
+                tx.finalize();
+                safe_json_from_tx(&tx, &self.entries, masses)
+        }
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+        use kaspa_addresses::{Address, Prefix, Version};
+        use kaspa_consensus_core::network::{NetworkId, NetworkType};
+        use crate::tx::generator::{TxGenOutput, TxGenUtxo};
+        use crate::tx::sign::p2pk_scripts;
+
+        const KEYS: [&str; 2] = ["0101010101010101010101010101010101010101010101010101010101010101", "0202020202020202020202020202020202020202020202020202020202020202"];
+
+        #[test]
+        fn placeholders_cover_mixed_schnorr_and_ecdsa_inputs() {
+                let pubkeys: Vec<_> = KEYS.iter().map(|k| crate::keypair_from_hex(k).unwrap().public_key()).collect();
+                let (schnorr_spk, _) = p2pk_scripts(&pubkeys[0]);
+                let (_, ecdsa_spk) = p2pk_scripts(&pubkeys[1]);
+                let to = Address::new(Prefix::Mainnet, Version::PubKeyECDSA, &pubkeys[1].serialize()).to_string();
+                let entry = TxGenEntry {
+                        outputs: vec![TxGenOutput { address: to, amount: 150_000_000 }],
+                        utxos: vec![TxGenUtxo::new([1; 32], 0, 100_000_000, schnorr_spk), TxGenUtxo::new([2; 32], 0, 100_000_000, ecdsa_spk.clone())],
+                        ..TxGenEntry::new(NetworkId::new(NetworkType::Mainnet))
+                };
+                let plan = TxPlan::new(&entry).unwrap();
+                assert_eq!(plan.tx.inputs.iter().map(|i| i.signature_script.len()).collect::<Vec<_>>(), vec![SCHNORR_SIG_SCRIPT_LEN, ECDSA_SIG_SCRIPT_LEN]);
+
+                let signer = InputSigner::matched(&KEYS.iter().map(|k| k.to_string()).collect::<Vec<_>>()).unwrap();
+                let mut signed = plan.tx.clone();
+                sign_inputs(&mut signed, &plan.entries, &signer, &plan.sign_plan(0x01).unwrap()).unwrap();
+                assert!(signed.inputs.iter().zip(plan.tx.inputs.iter()).all(|(s, p)| s.signature_script.len() <= p.signature_script.len()));
+                assert!(calc_tx_masses(&plan.mc, &signed, &plan.entries).max() <= plan.masses().max());
+                let safe = plan.sign(&signer, &plan.sign_plan(0x01).unwrap()).unwrap();
+                assert_eq!(safe.compute_mass, calc_tx_masses(&plan.mc, &signed, &plan.entries).compute.to_string());
+        }
+}
@@ -0,0 +1,310 @@
+use std::os::raw::{c_char, c_int};
+
+use kaspa_consensus_core::config::params::Params;
+use kaspa_consensus_core::hashing::sighash::SigHashReusedValuesUnsync;
+use kaspa_consensus_core::mass::MassCalculator;
+use kaspa_consensus_core::subnets::SubnetworkId;
+use kaspa_consensus_core::tx::{SignableTransaction, Transaction, TransactionInput, TransactionOutput, UtxoEntry};
+use kaspa_wallet_pskt::prelude::{Creator, Inner, InputBuilder, OutputBuilder, Signature, PSKT};
+use secp256k1::{ecdsa, schnorr, PublicKey, Secp256k1};
+
+use crate::parse_network_id;
+use crate::error::{ffi_guard, ffi_string, KisrError, KisrErrorCode};
+use crate::fee::calc_tx_masses;
+use super::generator::{c_str_arg, entry_snapshot, to_json_string, TxGenEntry};
+use super::plan::{safe_json_from_tx, TxPlan};
+use super::sign::{input_message, p2pk_scripts, sign_message, signature_script, InputSigner, RawSignature, SignPlan, ALGO_ECDSA, ALGO_SCHNORR};
+
+// PSKTs travel as the JSON serialization of kaspa-wallet-pskt's `Inner` (what `PSKT<ROLE>` flattens to), so any
+// role can pick them up. Only P2PK inputs are supported: each input is finalized from a single partial signature.
+// The format has no payload field, so a payload (e.g. a KISR envelope) rides as hex in the global proprietary
+// entry below; every role here reads the transaction back with it.
+const PAYLOAD_PROPRIETARY: &str = "kisr:payload";
+
+fn set_payload(json: &mut serde_json::Value, payload: &[u8]) -> Result<(), KisrError> {
+        let proprietaries = json.pointer_mut("/global/proprietaries").and_then(|v| v.as_object_mut())
+                .ok_or_else(|| KisrError::new(KisrErrorCode::Internal, "pskt has no global proprietaries"))?;
+        proprietaries.insert(PAYLOAD_PROPRIETARY.to_string(), hex::encode(payload).into());
+        Ok(())
+}
+
+fn pskt_payload(inner: &Inner) -> Result<Vec<u8>, KisrError> {
+        let proprietaries = serde_json::to_value(&inner.global.proprietaries).map_err(|e| KisrError::new(KisrErrorCode::Serialization, format!("serialization error: {}", e)))?;
+        match proprietaries.get(PAYLOAD_PROPRIETARY) {
+                None => Ok(vec![]),
+                Some(v) => v.as_str().and_then(|h| hex::decode(h).ok()).ok_or_else(|| KisrError::new(KisrErrorCode::InvalidHex, format!("invalid {} entry", PAYLOAD_PROPRIETARY))),
+        }
+}
+
+fn build_pskt(entry: &TxGenEntry, sighash_type_u8: u8) -> Result<String, KisrError> {
+        let TxPlan { entry, tx, entries, .. } = TxPlan::new(entry)?;
+        if entry.utxos.is_empty() { return Err(KisrError::new(KisrErrorCode::BuildFailed, "no utxos")); }
+        let plan = SignPlan::new(sighash_type_u8, &entry.input_sighash, None, tx.inputs.len())?;
+
+        let mut pskt = PSKT::<Creator>::default().constructor();
+        for (i, (inp, utxo)) in tx.inputs.iter().zip(entries).enumerate() {
+                let mut input = InputBuilder::default()
+                        .utxo_entry(utxo)
+                        .previous_outpoint(inp.previous_outpoint)
+                        .sig_op_count(inp.sig_op_count)
+                        .sighash_type(plan.sig_type(i))
+                        .build()
+                        .map_err(|e| KisrError::new(KisrErrorCode::BuildFailed, format!("input {}: {}", i, e)).with("inputIndex", i))?;
+                // The builder has no setter for it
+                input.sequence = Some(inp.sequence);
+                pskt = pskt.input(input);
+        }
+        for (i, out) in tx.outputs.iter().enumerate() {
+                let output = OutputBuilder::default()
+                        .amount(out.value)
+                        .script_public_key(out.script_public_key.clone())
+                        .build()
+                        .map_err(|e| KisrError::new(KisrErrorCode::BuildFailed, format!("output {}: {}", i, e)).with("outputIndex", i))?;
+                pskt = pskt.output(output);
+        }
+        let pskt = pskt.no_more_inputs().no_more_outputs();
+        let mut json = serde_json::to_value(&pskt).map_err(|e| KisrError::new(KisrErrorCode::Serialization, format!("serialization error: {}", e)))?;
+        if !tx.payload.is_empty() { set_payload(&mut json, &tx.payload)?; }
+        to_json_string(&json)
+}
+
+fn parse_inner(json: &str) -> Result<Inner, KisrError> {
+        serde_json::from_str::<Inner>(json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid pskt json: {}", e)))
+}
+
+fn incomplete_input(i: usize, what: &str) -> KisrError {
+        KisrError::new(KisrErrorCode::InvalidArgument, format!("input {} {}", i, what)).with("inputIndex", i)
+}
+
+// The transaction every role signs over; signature scripts come from final_script_sig when present
+fn pskt_tx(inner: &Inner, with_final_scripts: bool) -> Result<(Transaction, Vec<UtxoEntry>), KisrError> {
+        let mut inputs = Vec::with_capacity(inner.inputs.len());
+        let mut entries = Vec::with_capacity(inner.inputs.len());
+        for (i, inp) in inner.inputs.iter().enumerate() {
+                let utxo = inp.utxo_entry.clone().ok_or_else(|| incomplete_input(i, "has no utxo entry"))?;
+                let sig_script = if with_final_scripts {
+                        inp.final_script_sig.clone().ok_or_else(|| incomplete_input(i, "is not finalized"))?
+                } else {
+                        vec![]
+                };
+                inputs.push(TransactionInput::new(inp.previous_outpoint, sig_script, inp.sequence.unwrap_or(0), inp.sig_op_count.unwrap_or(1)));
+                entries.push(utxo);
+        }
+        let outputs = inner.outputs.iter().map(|o| TransactionOutput { value: o.amount, script_public_key: o.script_public_key.clone() }).collect();
+        let mut tx = Transaction::new(inner.global.tx_version, inputs, outputs, inner.global.fallback_lock_time.unwrap_or(0), SubnetworkId::default(), 0, pskt_payload(inner)?);
+        tx.finalize();
+        Ok((tx, entries))
+}
+
+fn to_pskt_signature(sig: RawSignature) -> Result<Signature, KisrError> {
+        match sig {
+                RawSignature::Schnorr(bytes) => schnorr::Signature::from_slice(&bytes).map(Signature::Schnorr).map_err(|_| KisrError::new(KisrErrorCode::InvalidSignature, "invalid schnorr signature")),
+                RawSignature::Ecdsa(sig) => Ok(Signature::ECDSA(sig)),
+        }
+}
+
+fn from_pskt_signature(sig: &Signature) -> RawSignature {
+        match sig {
+                Signature::Schnorr(sig) => RawSignature::Schnorr(*sig.as_ref()),
+                Signature::ECDSA(sig) => RawSignature::Ecdsa(*sig),
+        }
+}
+
+// Adds a partial signature for every input a key owns; inputs owned by other parties are left alone
+fn sign_pskt(json: &str, private_keys_json: &str) -> Result<String, KisrError> {
+        let mut inner = parse_inner(json)?;
+        let keys: Vec<String> = serde_json::from_str(private_keys_json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid keys json: {}", e)))?;
+        let signer = InputSigner::matched(&keys)?;
+        let (tx, entries) = pskt_tx(&inner, false)?;
+        let signable = SignableTransaction::with_entries(tx, entries.clone());
+        let secp = Secp256k1::new();
+        let mut reused = SigHashReusedValuesUnsync::new();
+        let mut signed = 0usize;
+        for (i, input) in inner.inputs.iter_mut().enumerate() {
+                let Some((keypair, ecdsa)) = signer.key_for(&entries[i]) else { continue; };
+                let msg = input_message(&signable, i, input.sighash_type, ecdsa, &mut reused)?;
+                input.partial_sigs.insert(keypair.public_key(), to_pskt_signature(sign_message(&secp, &msg, keypair, ecdsa))?);
+                signed += 1;
+        }
+        if signed == 0 { return Err(KisrError::new(KisrErrorCode::NoMatchingKey, "no key matches any input")); }
+        to_json_string(&inner)
+}
+
+// Attaches a signature produced elsewhere (custody service, hardware key) after checking it against the input's
+// sighash and UTXO script. Schnorr signatures are 64 bytes; ECDSA ones are 64-byte compact or DER.
+fn add_signature(json: &str, input_index: usize, public_key_hex: &str, signature_hex: &str, algo: u8) -> Result<String, KisrError> {
+        let mut inner = parse_inner(json)?;
+        if input_index >= inner.inputs.len() { return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("input {} out of range", input_index)).with("inputIndex", input_index)); }
+        let public_key = hex::decode(public_key_hex.trim()).ok().and_then(|b| PublicKey::from_slice(&b).ok()).ok_or_else(|| KisrError::new(KisrErrorCode::InvalidKey, "invalid public key"))?;
+        let sig_bytes = hex::decode(signature_hex.trim()).map_err(|_| KisrError::new(KisrErrorCode::InvalidHex, "invalid signature hex"))?;
+        let not_owner = || KisrError::new(KisrErrorCode::NoMatchingKey, format!("public key does not own input {}", input_index)).with("inputIndex", input_index);
+        let bad_signature = |msg: &str| KisrError::new(KisrErrorCode::InvalidSignature, msg.to_string()).with("inputIndex", input_index);
+        let (tx, entries) = pskt_tx(&inner, false)?;
+        let (schnorr_spk, ecdsa_spk) = p2pk_scripts(&public_key);
+        let script = entries[input_index].script_public_key.script();
+        let signable = SignableTransaction::with_entries(tx, entries.clone());
+        let secp = Secp256k1::verification_only();
+        let mut reused = SigHashReusedValuesUnsync::new();
+        let sig_type = inner.inputs[input_index].sighash_type;
+        let sig = match algo {
+                ALGO_SCHNORR => {
+                        if script != schnorr_spk.as_slice() { return Err(not_owner()); }
+                        let sig = schnorr::Signature::from_slice(&sig_bytes).map_err(|_| bad_signature("invalid schnorr signature"))?;
+                        let msg = input_message(&signable, input_index, sig_type, false, &mut reused)?;
+                        secp.verify_schnorr(&sig, &msg, &public_key.x_only_public_key().0).map_err(|_| bad_signature("signature does not verify"))?;
+                        Signature::Schnorr(sig)
+                }
+                ALGO_ECDSA => {
+                        if script != ecdsa_spk.as_slice() { return Err(not_owner()); }
+                        let sig = ecdsa::Signature::from_compact(&sig_bytes).or_else(|_| ecdsa::Signature::from_der(&sig_bytes)).map_err(|_| bad_signature("invalid ecdsa signature"))?;
+                        let msg = input_message(&signable, input_index, sig_type, true, &mut reused)?;
+                        secp.verify_ecdsa(&msg, &sig, &public_key).map_err(|_| bad_signature("signature does not verify"))?;
+                        Signature::ECDSA(sig)
+                }
+                other => return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("unknown signature algorithm {}", other)).with("algo", other)),
+        };
+        inner.inputs[input_index].partial_sigs.insert(public_key, sig);
+        to_json_string(&inner)
+}
+
+// Merges partial signatures from PSKTs of the same unsigned transaction
+fn combine(pskts_json: &str) -> Result<String, KisrError> {
+        let pskts: Vec<serde_json::Value> = serde_json::from_str(pskts_json).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid json: {}", e)))?;
+        let mut iter = pskts.into_iter().map(|v| serde_json::from_value::<Inner>(v).map_err(|e| KisrError::new(KisrErrorCode::InvalidJson, format!("invalid pskt json: {}", e))));
+        let mut combined = iter.next().ok_or_else(|| KisrError::new(KisrErrorCode::InvalidArgument, "no pskts"))??;
+        let id = pskt_tx(&combined, false)?.0.id();
+        for (n, other) in iter.enumerate() {
+                let other = other?;
+                if pskt_tx(&other, false)?.0.id() != id { return Err(KisrError::new(KisrErrorCode::StaleTransaction, format!("pskt {} is for a different transaction", n + 1)).with("position", n + 1)); }
+                for (mine, theirs) in combined.inputs.iter_mut().zip(other.inputs) {
+                        if mine.sighash_type.to_u8() != theirs.sighash_type.to_u8() { return Err(KisrError::new(KisrErrorCode::InvalidSighashType, "pskts disagree on an input's sighash type")); }
+                        mine.partial_sigs.extend(theirs.partial_sigs);
+                        if mine.final_script_sig.is_none() { mine.final_script_sig = theirs.final_script_sig; }
+                }
+        }
+        to_json_string(&combined)
+}
+
+// Turns each input's partial signature into its final signature script
+fn finalize(json: &str) -> Result<String, KisrError> {
+        let mut inner = parse_inner(json)?;
+        for (i, input) in inner.inputs.iter_mut().enumerate() {
+                if input.final_script_sig.is_some() { continue; }
+                let utxo = input.utxo_entry.as_ref().ok_or_else(|| incomplete_input(i, "has no utxo entry"))?;
+                let script = utxo.script_public_key.script();
+                let sig = input.partial_sigs.iter().find_map(|(pk, sig)| {
+                        let (schnorr_spk, ecdsa_spk) = p2pk_scripts(pk);
+                        match sig {
+                                Signature::Schnorr(_) if script == schnorr_spk.as_slice() => Some(sig),
+                                Signature::ECDSA(_) if script == ecdsa_spk.as_slice() => Some(sig),
+                                _ => None,
+                        }
+                }).ok_or_else(|| KisrError::new(KisrErrorCode::NoMatchingKey, format!("input {} has no signature from its owner", i)).with("inputIndex", i))?;
+                input.final_script_sig = Some(signature_script(&from_pskt_signature(sig), input.sighash_type));
+        }
+        to_json_string(&inner)
+}
+
+fn extract_safejson(json: &str, network: &str) -> Result<String, KisrError> {
+        let inner = parse_inner(json)?;
+        let network_id = parse_network_id(network).ok_or_else(|| KisrError::new(KisrErrorCode::InvalidNetwork, format!("unknown network {}", network)).with("network", network))?;
+        let params: Params = network_id.into();
+        let (tx, entries) = pskt_tx(&inner, true)?;
+        let masses = calc_tx_masses(&MassCalculator::new_with_consensus_params(&params), &tx, &entries);
+        to_json_string(&safe_json_from_tx(&tx, &entries, &masses))
+}
+
+// Creator/constructor role: the handle's transaction (inputs selected, fee and change settled) as an unsigned PSKT.
+// `sighash_type` applies to inputs without a kaspa_tx_generator_set_input_sighash override.
+#[no_mangle]
+pub extern "C" fn kaspa_tx_generator_build_pskt(gen: c_int, sighash_type: u8) -> *mut c_char {
+        ffi_guard("kaspa_tx_generator_build_pskt", || {
+                ffi_string("kaspa_tx_generator_build_pskt", entry_snapshot(gen).and_then(|entry| build_pskt(&entry, sighash_type)))
+        })
+}
+
+// Signer role: `private_keys_json` is a JSON array of hex private keys, matched to inputs by script
+#[no_mangle]
+pub extern "C" fn kaspa_pskt_sign(pskt_json: *const c_char, private_keys_json: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_pskt_sign", || {
+                let result = (|| sign_pskt(&c_str_arg(pskt_json, "pskt_json")?, &c_str_arg(private_keys_json, "private_keys_json")?))();
+                ffi_string("kaspa_pskt_sign", result)
+        })
+}
+
+// Signer role for keys outside the process: `public_key_hex` is the 33-byte compressed key, `algo` 0 = Schnorr, 1 = ECDSA
+#[no_mangle]
+pub extern "C" fn kaspa_pskt_add_signature(pskt_json: *const c_char, input_index: u32, public_key_hex: *const c_char, signature_hex: *const c_char, algo: u8) -> *mut c_char {
+        ffi_guard("kaspa_pskt_add_signature", || {
+                let result = (|| add_signature(&c_str_arg(pskt_json, "pskt_json")?, input_index as usize, &c_str_arg(public_key_hex, "public_key_hex")?, &c_str_arg(signature_hex, "signature_hex")?, algo))();
+                ffi_string("kaspa_pskt_add_signature", result)
+        })
+}
+
+// Combiner role: `pskts_json` is a JSON array of PSKTs
+#[no_mangle]
+pub extern "C" fn kaspa_pskt_combine(pskts_json: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_pskt_combine", || {
+                ffi_string("kaspa_pskt_combine", c_str_arg(pskts_json, "pskts_json").and_then(|json| combine(&json)))
+        })
+}
+
+#[no_mangle]
+pub extern "C" fn kaspa_pskt_finalize(pskt_json: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_pskt_finalize", || {
+                ffi_string("kaspa_pskt_finalize", c_str_arg(pskt_json, "pskt_json").and_then(|json| finalize(&json)))
+        })
+}
+
+// Extractor role: a finalized PSKT as SafeJSON, ready for kaspa_rpc_submit_safe_json. `network` sets the mass parameters.
+#[no_mangle]
+pub extern "C" fn kaspa_pskt_extract_safejson(pskt_json: *const c_char, network: *const c_char) -> *mut c_char {
+        ffi_guard("kaspa_pskt_extract_safejson", || {
+                let result = (|| extract_safejson(&c_str_arg(pskt_json, "pskt_json")?, &c_str_arg(network, "network")?))();
+                ffi_string("kaspa_pskt_extract_safejson", result)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+        use kaspa_addresses::Prefix;
+        use kaspa_consensus_core::network::{NetworkId, NetworkType};
+        use crate::tx::generator::{TxGenOutput, TxGenUtxo};
+
+        const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+        #[test]
+        fn payload_survives_every_role() {
+                let address = crate::schnorr_address(&crate::keypair_from_hex(KEY).unwrap(), Prefix::Mainnet);
+                let entry = TxGenEntry {
+                        change_address: Some(address.to_string()),
+                        outputs: vec![TxGenOutput { address: address.to_string(), amount: 50_000_000 }],
+                        utxos: vec![TxGenUtxo::new([7; 32], 1, 100_000_000, p2pk_scripts(&crate::keypair_from_hex(KEY).unwrap().public_key()).0)],
+                        payload: b"KISR-envelope".to_vec(),
+                        ..TxGenEntry::new(NetworkId::new(NetworkType::Mainnet))
+                };
+                let planned = TxPlan::new(&entry).unwrap().tx;
+                let pskt = build_pskt(&entry, 0x01).unwrap();
+                let signed = sign_pskt(&pskt, &serde_json::to_string(&[KEY]).unwrap()).unwrap();
+                let combined = combine(&serde_json::to_string(&[serde_json::from_str::<serde_json::Value>(&pskt).unwrap(), serde_json::from_str(&signed).unwrap()]).unwrap()).unwrap();
+                let safe: crate::SafeJsonTx = serde_json::from_str(&extract_safejson(&finalize(&combined).unwrap(), "mainnet").unwrap()).unwrap();
+                assert_eq!(safe.payload, hex::encode(b"KISR-envelope"));
+                assert_eq!(safe.id, planned.id().to_string());
+                assert_eq!(safe.inputs[0].signature_script.len(), 66 * 2);
+        }
+
+        #[test]
+        fn combine_rejects_other_transactions() {
+                let address = crate::schnorr_address(&crate::keypair_from_hex(KEY).unwrap(), Prefix::Mainnet);
+                let mut entry = TxGenEntry {
+                        outputs: vec![TxGenOutput { address: address.to_string(), amount: 50_000_000 }],
+                        utxos: vec![TxGenUtxo::new([7; 32], 1, 100_000_000, p2pk_scripts(&crate::keypair_from_hex(KEY).unwrap().public_key()).0)],
+                        ..TxGenEntry::new(NetworkId::new(NetworkType::Mainnet))
+                };
+                let a: serde_json::Value = serde_json::from_str(&build_pskt(&entry, 0x01).unwrap()).unwrap();
+                entry.payload = vec![1];
+                let b: serde_json::Value = serde_json::from_str(&build_pskt(&entry, 0x01).unwrap()).unwrap();
+                assert_eq!(combine(&serde_json::to_string(&[a, b]).unwrap()).unwrap_err().code, KisrErrorCode::StaleTransaction);
+        }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+use std::os::raw::{c_char, c_int};
+use std::sync::{Mutex, OnceLock};
+
+use kaspa_consensus_core::tx::Transaction;
+
+use crate::error::{fail, ffi_guard, lock_unpoisoned, KisrError};
+use crate::tx::generator::{c_str_arg, parse_txid};
+
+pub(crate) type OutpointKey = ([u8; 32], u32);
+
+// Process-wide: outpoints held by pending flows (e.g. a KISRUTXO awaiting redemption) are skipped by every
+// generator handle's selection until released, including UTXOs added explicitly with add_utxo. Only the entry
+// holding the reservation (see `TxGenEntry::held`) spends them.
+fn __reserved() -> &'static Mutex<HashSet<OutpointKey>> {
+        static SET: OnceLock<Mutex<HashSet<OutpointKey>>> = OnceLock::new();
+        SET.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+pub(crate) fn with_reserved<R>(f: impl FnOnce(&mut HashSet<OutpointKey>) -> R) -> R {
+        let mut set = lock_unpoisoned(__reserved());
+        f(&mut set)
+}
+
+pub(crate) fn reserve(key: OutpointKey) -> bool { with_reserved(|r| r.insert(key)) }
+
+pub(crate) fn release(key: &OutpointKey) -> bool { with_reserved(|r| r.remove(key)) }
+
+// Once a spend is submitted its outpoints leave the UTXO set; there is nothing left to hold
+#[cfg_attr(not(feature = "rpc"), allow(dead_code))]
+pub(crate) fn release_spent(tx: &Transaction) {
+        with_reserved(|r| {
+                for inp in tx.inputs.iter() { r.remove(&(inp.previous_outpoint.transaction_id.as_bytes(), inp.previous_outpoint.index)); }
+        })
+}
+
+// A reservation scoped to a flow: dropped on an error path it is released, `keep` leaves it for a later spend.
+// An outpoint that was already reserved by someone else is never released by this guard.
+#[cfg_attr(not(feature = "rpc"), allow(dead_code))]
+pub(crate) struct Reservation {
+        key: OutpointKey,
+        owned: bool,
+}
+
+#[cfg_attr(not(feature = "rpc"), allow(dead_code))]
+impl Reservation {
+        pub(crate) fn hold(key: OutpointKey) -> Self { Reservation { key, owned: reserve(key) } }
+
+        pub(crate) fn keep(mut self) { self.owned = false; }
+}
+
+impl Drop for Reservation {
+        fn drop(&mut self) {
+                if self.owned { release(&self.key); }
+        }
+}
+
+pub(crate) fn parse_outpoint(txid_hex: *const c_char, index: u32) -> Result<OutpointKey, KisrError> {
+        Ok((parse_txid(&c_str_arg(txid_hex, "transaction_id")?)?, index))
+}
+
+// Returns 0 when newly reserved, 1 when it already was, negative on bad input
+#[no_mangle]
+pub extern "C" fn kaspa_outpoint_reserve(txid_hex: *const c_char, index: u32) -> c_int {
+        ffi_guard("kaspa_outpoint_reserve", || {
+                match parse_outpoint(txid_hex, index) { Ok(key) => if reserve(key) { 0 } else { 1 }, Err(e) => fail("kaspa_outpoint_reserve", e) }
+        })
+}
+
+// Returns 0 when released, 1 when it was not reserved, negative on bad input
+#[no_mangle]
+pub extern "C" fn kaspa_outpoint_release(txid_hex: *const c_char, index: u32) -> c_int {
+        ffi_guard("kaspa_outpoint_release", || {
+                match parse_outpoint(txid_hex, index) { Ok(key) => if release(&key) { 0 } else { 1 }, Err(e) => fail("kaspa_outpoint_release", e) }
+        })
+}
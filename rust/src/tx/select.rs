@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use std::os::raw::c_int;
+
+use kaspa_consensus_core::mass::MassCalculator;
+
+use crate::error::{KisrError, KisrErrorCode};
+use crate::fee::{calc_tx_masses, min_fee_for_mass};
+use super::generator::{TxGenEntry, TxGenUtxo};
+use super::plan::assemble_tx;
+use super::reserve::{with_reserved, OutpointKey};
+
+pub const KASPA_SELECT_NONE: c_int = 0;
+pub const KASPA_SELECT_CLOSEST_SINGLE: c_int = 1;
+pub const KASPA_SELECT_LARGEST_FIRST: c_int = 2;
+pub const KASPA_SELECT_SMALLEST_FIRST: c_int = 3;
+pub const KASPA_SELECT_BRANCH_AND_BOUND: c_int = 4;
+
+const BNB_MAX_TRIES: usize = 100_000;
+const RESERVE_ATTEMPTS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum SelectionStrategy {
+        // Spend exactly the UTXOs added with add_utxo
+        None,
+        // Smallest single candidate that covers outputs + fee (exact match when one exists)
+        ClosestSingle,
+        LargestFirst,
+        // Consumes dust first; pair with the chain builder when the result exceeds the mass limit
+        SmallestFirst,
+        // Searches for a change-less subset, falling back to largest-first
+        BranchAndBound,
+}
+
+impl SelectionStrategy {
+        pub fn from_code(code: c_int) -> Option<Self> {
+                match code {
+                        KASPA_SELECT_NONE => Some(SelectionStrategy::None),
+                        KASPA_SELECT_CLOSEST_SINGLE => Some(SelectionStrategy::ClosestSingle),
+                        KASPA_SELECT_LARGEST_FIRST => Some(SelectionStrategy::LargestFirst),
+                        KASPA_SELECT_SMALLEST_FIRST => Some(SelectionStrategy::SmallestFirst),
+                        KASPA_SELECT_BRANCH_AND_BOUND => Some(SelectionStrategy::BranchAndBound),
+                        _ => None,
+                }
+        }
+}
+
+struct Pricer<'a> {
+        entry: &'a TxGenEntry,
+        mc: MassCalculator,
+        total_output: u64,
+}
+
+impl<'a> Pricer<'a> {
+        fn new(entry: &'a TxGenEntry) -> Self {
+                let params = entry.params();
+                Pricer { entry, mc: MassCalculator::new_with_consensus_params(&params), total_output: entry.outputs.iter().map(|o| o.amount).sum() }
+        }
+
+        // Outputs plus the fee of the change-less transaction spending `utxos`
+        fn required(&self, utxos: &[TxGenUtxo]) -> Option<u64> {
+                self.required_with_change(utxos, None)
+        }
+
+        fn required_with_change(&self, utxos: &[TxGenUtxo], change: Option<u64>) -> Option<u64> {
+                let mut trial = self.entry.clone();
+                trial.utxos = utxos.to_vec();
+                let (tx, entries) = assemble_tx(&trial, change)?;
+                let fee = min_fee_for_mass(calc_tx_masses(&self.mc, &tx, &entries).max(), self.entry.rate());
+                Some(self.total_output.saturating_add(fee))
+        }
+
+        fn compute_mass(&self, utxos: &[TxGenUtxo]) -> Option<u64> {
+                let mut trial = self.entry.clone();
+                trial.utxos = utxos.to_vec();
+                let (tx, entries) = assemble_tx(&trial, None)?;
+                Some(calc_tx_masses(&self.mc, &tx, &entries).compute)
+        }
+
+        fn covers(&self, utxos: &[TxGenUtxo]) -> bool {
+                let total: u64 = utxos.iter().map(|u| u.amount).sum();
+                total >= self.total_output && self.required(utxos).is_some_and(|r| total >= r)
+        }
+}
+
+fn concat(base: &[TxGenUtxo], chosen: &[TxGenUtxo]) -> Vec<TxGenUtxo> {
+        let mut all = base.to_vec();
+        all.extend_from_slice(chosen);
+        all
+}
+
+// Compute mass grows by a fixed amount per input, priced on its own against the input-less transaction. The running
+// total is a lower bound on the fee, so the exact (storage-aware) price is only taken once it can be covered.
+fn accumulate(pricer: &Pricer, base: &[TxGenUtxo], ordered: &[TxGenUtxo]) -> Option<Vec<TxGenUtxo>> {
+        let shell = pricer.compute_mass(&[])?;
+        let mut compute = pricer.compute_mass(base)?;
+        let mut total: u64 = base.iter().map(|u| u.amount).sum();
+        let mut all = base.to_vec();
+        for c in ordered.iter() {
+                compute += pricer.compute_mass(std::slice::from_ref(c))?.saturating_sub(shell);
+                total = total.saturating_add(c.amount);
+                all.push(c.clone());
+                let lower_bound = pricer.total_output.saturating_add(min_fee_for_mass(compute, pricer.entry.rate()));
+                if total >= lower_bound && pricer.covers(&all) { return Some(all); }
+        }
+        None
+}
+
+fn closest_single(pricer: &Pricer, base: &[TxGenUtxo], ascending: &[TxGenUtxo]) -> Option<Vec<TxGenUtxo>> {
+        ascending.iter().map(|c| concat(base, std::slice::from_ref(c))).find(|all| pricer.covers(all))
+}
+
+// Depth-first search over candidates (largest first) for a subset landing in [target, target + cost_of_change],
+// i.e. one that needs no change output. Fees are linearised per input; the result is re-checked exactly.
+fn branch_and_bound(pricer: &Pricer, base: &[TxGenUtxo], descending: &[TxGenUtxo]) -> Option<Vec<TxGenUtxo>> {
+        if descending.len() < 2 { return None; }
+        let one = pricer.required(&concat(base, &descending[..1]))?;
+        let two = pricer.required(&concat(base, &descending[..2]))?;
+        let fee_per_input = two.saturating_sub(one);
+        let change_probe = descending[0].amount;
+        let cost_of_change = pricer.required_with_change(&concat(base, &descending[..1]), Some(change_probe))?.saturating_sub(one);
+        let base_total: u64 = base.iter().map(|u| u.amount).sum();
+        let target = one.saturating_sub(fee_per_input).saturating_sub(base_total) as i128;
+        let upper = target + cost_of_change as i128;
+        let values: Vec<i128> = descending.iter().map(|u| u.amount as i128 - fee_per_input as i128).collect();
+        let mut suffix = vec![0i128; values.len() + 1];
+        for i in (0..values.len()).rev() { suffix[i] = suffix[i + 1] + values[i].max(0); }
+
+        struct Search<'v> { values: &'v [i128], suffix: &'v [i128], target: i128, upper: i128, tries: usize, best: Option<(i128, Vec<usize>)> }
+        fn dfs(s: &mut Search, i: usize, sum: i128, picked: &mut Vec<usize>) {
+                s.tries += 1;
+                if s.tries > BNB_MAX_TRIES || sum > s.upper { return; }
+                if sum >= s.target {
+                        let waste = sum - s.target;
+                        if s.best.as_ref().is_none_or(|(w, _)| waste < *w) { s.best = Some((waste, picked.clone())); }
+                        return;
+                }
+                if i == s.values.len() || sum + s.suffix[i] < s.target { return; }
+                if s.values[i] > 0 {
+                        picked.push(i);
+                        dfs(s, i + 1, sum + s.values[i], picked);
+                        picked.pop();
+                }
+                dfs(s, i + 1, sum, picked);
+        }
+        let mut search = Search { values: &values, suffix: &suffix, target, upper, tries: 0, best: None };
+        dfs(&mut search, 0, 0, &mut Vec::new());
+        let (_, picked) = search.best?;
+        let chosen: Vec<TxGenUtxo> = picked.into_iter().map(|i| descending[i].clone()).collect();
+        let all = concat(base, &chosen);
+        if pricer.covers(&all) { Some(all) } else { None }
+}
+
+// The full input list for a build: the explicitly added UTXOs plus whatever the strategy picks from the candidates.
+// Excluded outpoints are never spent; reserved ones are dropped unless the entry holds them; immature coinbase UTXOs
+// are skipped as candidates and rejected when added directly.
+pub(crate) fn select_inputs(entry: &TxGenEntry) -> Result<Vec<TxGenUtxo>, KisrError> {
+        // The search runs against a copy so other flows are not blocked behind it
+        let reserved = with_reserved(|r| r.clone());
+        select_inputs_with(entry, &reserved)
+}
+
+// Selects and reserves the picked candidates, so concurrent flows cannot pick the same outpoints. The search runs
+// outside the lock; if another flow reserved one of the picks meanwhile, it searches again.
+pub(crate) fn reserve_selection(entry: &TxGenEntry) -> Result<Vec<TxGenUtxo>, KisrError> {
+        for _ in 0..RESERVE_ATTEMPTS {
+                let picked = select_inputs(entry)?;
+                let keys: Vec<OutpointKey> = picked.iter()
+                        .filter(|u| entry.candidates.iter().any(|c| c.txid == u.txid && c.index == u.index))
+                        .map(|u| (u.txid, u.index))
+                        .collect();
+                let claimed = with_reserved(|reserved| {
+                        if keys.iter().any(|k| reserved.contains(k)) { return false; }
+                        reserved.extend(keys.iter().copied());
+                        true
+                });
+                if claimed { return Ok(picked); }
+        }
+        Err(KisrError::new(KisrErrorCode::BuildFailed, "selected candidates kept being reserved by concurrent flows").with("attempts", RESERVE_ATTEMPTS))
+}
+
+fn select_inputs_with(entry: &TxGenEntry, reserved: &HashSet<OutpointKey>) -> Result<Vec<TxGenUtxo>, KisrError> {
+        let spendable = |u: &TxGenUtxo| {
+                let key = (u.txid, u.index);
+                !entry.excluded.contains(&key) && (!reserved.contains(&key) || entry.held.contains(&key))
+        };
+        let base: Vec<TxGenUtxo> = entry.utxos.iter().filter(|u| spendable(u)).cloned().collect();
+        if let Some(u) = base.iter().find(|u| !entry.is_mature(u)) {
+                return Err(KisrError::new(KisrErrorCode::ImmatureCoinbase, format!("coinbase UTXO {}:{} is not mature (virtual DAA score {})", hex::encode(u.txid), u.index,
+                        entry.virtual_daa_score.map(|v| v.to_string()).unwrap_or_else(|| "not set".to_string())))
+                        .with("transactionId", hex::encode(u.txid)).with("index", u.index).with("blockDaaScore", u.block_daa_score.to_string()));
+        }
+        if entry.strategy == SelectionStrategy::None || entry.candidates.is_empty() { return Ok(base); }
+        let pricer = Pricer::new(entry);
+        if !base.is_empty() && pricer.covers(&base) { return Ok(base); }
+        let mut pool: Vec<TxGenUtxo> = entry.candidates.iter()
+                .filter(|c| !base.iter().any(|u| u.txid == c.txid && u.index == c.index))
+                .filter(|c| spendable(c))
+                .filter(|c| entry.is_mature(c))
+                .cloned()
+                .collect();
+        pool.sort_by_key(|u| u.amount);
+        let descending: Vec<TxGenUtxo> = pool.iter().rev().cloned().collect();
+        let picked = match entry.strategy {
+                SelectionStrategy::None => unreachable!(),
+                SelectionStrategy::ClosestSingle => closest_single(&pricer, &base, &pool).or_else(|| accumulate(&pricer, &base, &descending)),
+                SelectionStrategy::LargestFirst => accumulate(&pricer, &base, &descending),
+                SelectionStrategy::SmallestFirst => accumulate(&pricer, &base, &pool),
+                SelectionStrategy::BranchAndBound => branch_and_bound(&pricer, &base, &descending).or_else(|| accumulate(&pricer, &base, &descending)),
+        };
+        picked.ok_or_else(|| {
+                let available: u64 = base.iter().chain(pool.iter()).map(|u| u.amount).sum();
+                KisrError::new(KisrErrorCode::InsufficientFunds, format!("insufficient funds: {} available for {} in outputs plus fee", available, pricer.total_output))
+                        .with("available", available.to_string()).with("totalOutput", pricer.total_output.to_string())
+        })
+}
+
+pub(crate) fn resolve_inputs(entry: &TxGenEntry) -> Result<TxGenEntry, KisrError> {
+        let utxos = select_inputs(entry)?;
+        let mut resolved = entry.clone();
+        resolved.utxos = utxos;
+        resolved.candidates.clear();
+        resolved.strategy = SelectionStrategy::None;
+        Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+        use super::*;
+        use kaspa_addresses::Prefix;
+        use kaspa_consensus_core::network::{NetworkId, NetworkType};
+        use kaspa_txscript::pay_to_address_script;
+        use crate::tx::generator::TxGenOutput;
+
+        const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+        fn entry(output: u64, strategy: SelectionStrategy) -> TxGenEntry {
+                let address = crate::schnorr_address(&crate::keypair_from_hex(KEY).unwrap(), Prefix::Mainnet).to_string();
+                TxGenEntry {
+                        change_address: Some(address.clone()),
+                        outputs: vec![TxGenOutput { address, amount: output }],
+                        strategy,
+                        ..TxGenEntry::new(NetworkId::new(NetworkType::Mainnet))
+                }
+        }
+
+        fn utxo(tag: u8, amount: u64) -> TxGenUtxo {
+                let address = crate::schnorr_address(&crate::keypair_from_hex(KEY).unwrap(), Prefix::Mainnet);
+                TxGenUtxo::new([tag; 32], 0, amount, pay_to_address_script(&address).script().to_vec())
+        }
+
+        fn tags(utxos: &[TxGenUtxo]) -> Vec<u8> { utxos.iter().map(|u| u.txid[0]).collect() }
+
+        #[test]
+        fn branch_and_bound_finds_a_change_less_subset() {
+                let mut e = entry(1_000_000_000, SelectionStrategy::BranchAndBound);
+                // Two mid-sized UTXOs that pay the output plus the fee of their own change-less transaction exactly
+                let fee_free = Pricer::new(&e).required(&[utxo(2, 600_000_000), utxo(3, 1)]).unwrap();
+                e.candidates = vec![utxo(1, 5_000_000_000), utxo(2, 600_000_000), utxo(3, fee_free - 600_000_000), utxo(4, 10_000_000)];
+                let picked = select_inputs_with(&e, &HashSet::new()).unwrap();
+                assert_eq!(tags(&picked), vec![2, 3]);
+                assert_eq!(picked.iter().map(|u| u.amount).sum::<u64>(), Pricer::new(&e).required(&picked).unwrap());
+                // Largest-first takes the big UTXO and leaves change
+                e.strategy = SelectionStrategy::LargestFirst;
+                assert_eq!(tags(&select_inputs_with(&e, &HashSet::new()).unwrap()), vec![1]);
+        }
+
+        #[test]
+        fn branch_and_bound_falls_back_to_largest_first() {
+                let mut e = entry(1_000_000_000, SelectionStrategy::BranchAndBound);
+                e.candidates = vec![utxo(1, 3_000_000_000), utxo(2, 2_000_000_000), utxo(3, 700_000_000)];
+                assert_eq!(tags(&select_inputs_with(&e, &HashSet::new()).unwrap()), vec![1]);
+                // No subset of one: the fallback accumulates
+                e.candidates = vec![utxo(1, 600_000_000), utxo(2, 500_000_000), utxo(3, 100_000)];
+                assert_eq!(tags(&select_inputs_with(&e, &HashSet::new()).unwrap()), vec![1, 2]);
+        }
+
+        #[test]
+        fn excluded_and_reserved_outpoints_are_never_picked() {
+                let mut e = entry(1_000_000_000, SelectionStrategy::ClosestSingle);
+                e.candidates = vec![utxo(1, 1_100_000_000), utxo(2, 1_200_000_000), utxo(3, 1_300_000_000), utxo(4, 9_000_000_000)];
+                assert_eq!(tags(&select_inputs_with(&e, &HashSet::new()).unwrap()), vec![1]);
+                e.excluded.insert(([1; 32], 0));
+                let reserved: HashSet<OutpointKey> = [([2; 32], 0)].into_iter().collect();
+                assert_eq!(tags(&select_inputs_with(&e, &reserved).unwrap()), vec![3]);
+                // Excluded and reserved UTXOs are dropped even when added explicitly, unless the entry holds the reservation
+                e.utxos = vec![utxo(1, 5_000_000_000), utxo(2, 5_000_000_000), utxo(5, 5_000_000_000)];
+                assert_eq!(tags(&select_inputs_with(&e, &reserved).unwrap()), vec![5]);
+                e.held.insert(([2; 32], 0));
+                assert_eq!(tags(&select_inputs_with(&e, &reserved).unwrap()), vec![2, 5]);
+                e.utxos.clear();
+                e.held.clear();
+                e.excluded.extend([([3; 32], 0), ([4; 32], 0)]);
+                let err = select_inputs_with(&e, &reserved).unwrap_err();
+                assert_eq!(err.code, KisrErrorCode::InsufficientFunds);
+        }
+
+        #[test]
+        fn reserve_selection_hands_each_candidate_to_one_flow() {
+                // Tags no other test uses: the reservation table is process-wide
+                let mut e = entry(1_000_000_000, SelectionStrategy::ClosestSingle);
+                e.candidates = vec![utxo(0xe1, 2_000_000_000), utxo(0xe2, 3_000_000_000)];
+                assert_eq!(tags(&reserve_selection(&e).unwrap()), vec![0xe1]);
+                assert_eq!(tags(&reserve_selection(&e).unwrap()), vec![0xe2]);
+                assert_eq!(reserve_selection(&e).unwrap_err().code, KisrErrorCode::InsufficientFunds);
+                {
+                        let _guard = crate::tx::reserve::Reservation::hold(([0xe3; 32], 0));
+                        assert!(with_reserved(|r| r.contains(&([0xe3; 32], 0))));
+                }
+                assert!(!with_reserved(|r| r.contains(&([0xe3; 32], 0))));
+                for tag in [0xe1, 0xe2] { assert!(crate::tx::reserve::release(&([tag; 32], 0))); }
+        }
+
+        #[test]
+        fn accumulate_stops_at_the_first_covering_prefix() {
+                let e = entry(1_000_000_000, SelectionStrategy::SmallestFirst);
+                let pricer = Pricer::new(&e);
+                let pool: Vec<TxGenUtxo> = (1..=60u8).map(|i| utxo(i, 20_000_000 + i as u64 * 100_000)).collect();
+                let picked = accumulate(&pricer, &[], &pool).unwrap();
+                let shortest = (1..=pool.len()).find(|&n| pricer.covers(&pool[..n])).unwrap();
+                assert_eq!(picked.len(), shortest);
+                assert!(accumulate(&pricer, &[], &pool[..10]).is_none());
+        }
+}
@@ -0,0 +1,246 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::c_void;
+use std::os::raw::c_int;
+
+use kaspa_addresses as kaddr;
+use kaspa_consensus_core::hashing::sighash::{calc_ecdsa_signature_hash, calc_schnorr_signature_hash, SigHashReusedValuesUnsync};
+use kaspa_consensus_core::hashing::sighash_type::SigHashType;
+use kaspa_consensus_core::tx::{SignableTransaction, Transaction, UtxoEntry};
+use kaspa_txscript::pay_to_address_script;
+use secp256k1::{Keypair, Message, PublicKey, Secp256k1};
+
+use crate::{keypair_from_hex, script_push_data};
+use crate::error::{KisrError, KisrErrorCode};
+
+pub const SIGHASH_ALL: u8 = 0x01;
+pub const ALGO_SCHNORR: u8 = 0;
+pub const ALGO_ECDSA: u8 = 1;
+
+// Which inputs get signed and with which sighash type; inputs left out keep an empty signature script
+pub(crate) struct SignPlan {
+        default: SigHashType,
+        per_input: HashMap<usize, SigHashType>,
+        only: Option<HashSet<usize>>,
+}
+
+impl SignPlan {
+        pub(crate) fn new(default_u8: u8, per_input: &HashMap<u32, u8>, only: Option<&HashSet<u32>>, input_count: usize) -> Result<Self, KisrError> {
+                let default = parse_sighash(default_u8)?;
+                let mut overrides = HashMap::with_capacity(per_input.len());
+                for (&i, &t) in per_input.iter() {
+                        if i as usize >= input_count { return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("sighash set for input {} but the transaction has {} inputs", i, input_count)).with("inputIndex", i)); }
+                        overrides.insert(i as usize, parse_sighash(t)?);
+                }
+                if let Some(i) = only.and_then(|o| o.iter().find(|&&i| i as usize >= input_count)) {
+                        return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("input {} selected for signing but the transaction has {} inputs", i, input_count)).with("inputIndex", *i));
+                }
+                Ok(SignPlan { default, per_input: overrides, only: only.map(|o| o.iter().map(|&i| i as usize).collect()) })
+        }
+
+        pub(crate) fn signs(&self, input_index: usize) -> bool { self.only.as_ref().is_none_or(|o| o.contains(&input_index)) }
+
+        pub(crate) fn sig_type(&self, input_index: usize) -> SigHashType { self.per_input.get(&input_index).copied().unwrap_or(self.default) }
+}
+
+pub(crate) fn parse_sighash(sighash_type_u8: u8) -> Result<SigHashType, KisrError> {
+        SigHashType::from_u8(sighash_type_u8).map_err(|_| KisrError::new(KisrErrorCode::InvalidSighashType, format!("invalid sighash type {:#04x}", sighash_type_u8)))
+}
+
+// The Schnorr and ECDSA P2PK scripts a public key can own; the address prefix does not affect the script
+pub(crate) fn p2pk_scripts(public_key: &PublicKey) -> (Vec<u8>, Vec<u8>) {
+        let (xonly, _) = public_key.x_only_public_key();
+        let schnorr_addr = kaddr::Address::new(kaddr::Prefix::Mainnet, kaddr::Version::PubKey, &xonly.serialize());
+        let ecdsa_addr = kaddr::Address::new(kaddr::Prefix::Mainnet, kaddr::Version::PubKeyECDSA, &public_key.serialize());
+        (pay_to_address_script(&schnorr_addr).script().to_vec(), pay_to_address_script(&ecdsa_addr).script().to_vec())
+}
+
+// How each input finds its key
+pub(crate) enum InputSigner {
+        // One key for every input with a fixed algorithm; the UTXO scripts are not checked
+        Single { keypair: Keypair, ecdsa: bool },
+        // Each input is signed by the key whose P2PK script (Schnorr or ECDSA) matches its UTXO
+        Matched(Vec<MatchedKey>),
+        // Keys held outside the process; the algorithm follows each UTXO's P2PK script
+        External { callback: KaspaSignCallback, user_data: *mut c_void },
+        // Signatures already produced over kaspa_tx_generator_sighashes, by input index
+        Provided(HashMap<usize, Vec<u8>>),
+}
+
+// Writes the 64-byte signature (Schnorr, or compact ECDSA) of `sighash` (32 bytes) to `signature_out` and returns 0.
+// `algo` is 0 for Schnorr and 1 for ECDSA; the UTXO script is passed so the callee can pick the key.
+pub type KaspaSignCallback = extern "C" fn(
+        user_data: *mut c_void,
+        input_index: u32,
+        algo: u8,
+        sighash: *const u8,
+        script_public_key: *const u8,
+        script_public_key_len: usize,
+        signature_out: *mut u8,
+) -> c_int;
+
+pub(crate) struct MatchedKey {
+        keypair: Keypair,
+        schnorr_spk: Vec<u8>,
+        ecdsa_spk: Vec<u8>,
+}
+
+impl InputSigner {
+        pub(crate) fn single(private_key_hex: &str, algo: u8) -> Result<Self, KisrError> {
+                let ecdsa = match algo { ALGO_SCHNORR => false, ALGO_ECDSA => true, other => return Err(KisrError::new(KisrErrorCode::InvalidArgument, format!("unknown signature algorithm {}", other))) };
+                let keypair = keypair_from_hex(private_key_hex).map_err(|e| KisrError::new(KisrErrorCode::InvalidKey, e))?;
+                Ok(InputSigner::Single { keypair, ecdsa })
+        }
+
+        pub(crate) fn matched(private_keys_hex: &[String]) -> Result<Self, KisrError> {
+                if private_keys_hex.is_empty() { return Err(KisrError::new(KisrErrorCode::InvalidArgument, "no private keys")); }
+                let mut keys = Vec::with_capacity(private_keys_hex.len());
+                for (i, sk) in private_keys_hex.iter().enumerate() {
+                        let keypair = keypair_from_hex(sk).map_err(|e| KisrError::new(KisrErrorCode::InvalidKey, format!("key {}: {}", i, e)).with("keyIndex", i))?;
+                        let (schnorr_spk, ecdsa_spk) = p2pk_scripts(&keypair.public_key());
+                        keys.push(MatchedKey { keypair, schnorr_spk, ecdsa_spk });
+                }
+                Ok(InputSigner::Matched(keys))
+        }
+
+        // Whether `entry` is signed with ECDSA, or None when this signer cannot sign it
+        fn algo_for(&self, entry: &UtxoEntry) -> Option<bool> {
+                match self {
+                        InputSigner::Single { ecdsa, .. } => Some(*ecdsa),
+                        InputSigner::Matched(_) => self.key_for(entry).map(|(_, ecdsa)| ecdsa),
+                        InputSigner::External { .. } | InputSigner::Provided(_) => p2pk_kind(entry.script_public_key.script()),
+                }
+        }
+
+        fn sign(&self, secp: &Secp256k1<secp256k1::All>, input_index: usize, entry: &UtxoEntry, msg: &Message, ecdsa: bool) -> Result<RawSignature, KisrError> {
+                let script = entry.script_public_key.script();
+                let sig = match self {
+                        InputSigner::Single { .. } | InputSigner::Matched(_) => {
+                                let (keypair, _) = self.key_for(entry).ok_or_else(|| no_matching_key(input_index))?;
+                                return Ok(sign_message(secp, msg, keypair, ecdsa));
+                        }
+                        InputSigner::External { callback, user_data } => {
+                                let mut out = [0u8; 64];
+                                let rc = callback(*user_data, input_index as u32, if ecdsa { ALGO_ECDSA } else { ALGO_SCHNORR }, msg.as_ref().as_ptr(), script.as_ptr(), script.len(), out.as_mut_ptr());
+                                if rc != 0 { return Err(KisrError::new(KisrErrorCode::SignerFailed, format!("external signer failed for input {} ({})", input_index, rc)).with("inputIndex", input_index).with("signerCode", rc)); }
+                                raw_signature(&out, ecdsa).map_err(|e| e.with("inputIndex", input_index))?
+                        }
+                        InputSigner::Provided(sigs) => {
+                                let bytes = sigs.get(&input_index).ok_or_else(|| KisrError::new(KisrErrorCode::InvalidSignature, format!("no signature for input {}", input_index)).with("inputIndex", input_index))?;
+                                raw_signature(bytes, ecdsa).map_err(|e| e.with("inputIndex", input_index))?
+                        }
+                };
+                // Keys outside the process are checked against the UTXO before their signatures are used
+                if !verify_p2pk(secp, script, msg, &sig) { return Err(KisrError::new(KisrErrorCode::InvalidSignature, format!("signature for input {} does not verify", input_index)).with("inputIndex", input_index)); }
+                Ok(sig)
+        }
+
+        pub(crate) fn key_for(&self, entry: &UtxoEntry) -> Option<(&Keypair, bool)> {
+                match self {
+                        InputSigner::Single { keypair, ecdsa } => Some((keypair, *ecdsa)),
+                        InputSigner::Matched(keys) => {
+                                let script = entry.script_public_key.script();
+                                keys.iter().find_map(|k| {
+                                        if script == k.schnorr_spk.as_slice() { Some((&k.keypair, false)) }
+                                        else if script == k.ecdsa_spk.as_slice() { Some((&k.keypair, true)) }
+                                        else { None }
+                                })
+                        }
+                        InputSigner::External { .. } | InputSigner::Provided(_) => None,
+                }
+        }
+}
+
+// Some(false) for a Schnorr P2PK script, Some(true) for an ECDSA one
+pub(crate) fn p2pk_kind(script: &[u8]) -> Option<bool> {
+        match script {
+                [0x20, .., 0xac] if script.len() == 34 => Some(false),
+                [0x21, .., 0xab] if script.len() == 35 => Some(true),
+                _ => None,
+        }
+}
+
+// Schnorr signatures are 64 bytes; ECDSA ones are 64-byte compact or DER
+pub(crate) fn raw_signature(bytes: &[u8], ecdsa: bool) -> Result<RawSignature, KisrError> {
+        if ecdsa {
+                secp256k1::ecdsa::Signature::from_compact(bytes).or_else(|_| secp256k1::ecdsa::Signature::from_der(bytes))
+                        .map(RawSignature::Ecdsa).map_err(|_| KisrError::new(KisrErrorCode::InvalidSignature, "invalid ecdsa signature"))
+        } else {
+                <[u8; 64]>::try_from(bytes).map(RawSignature::Schnorr).map_err(|_| KisrError::new(KisrErrorCode::InvalidSignature, "schnorr signature must be 64 bytes"))
+        }
+}
+
+fn verify_p2pk(secp: &Secp256k1<secp256k1::All>, script: &[u8], msg: &Message, sig: &RawSignature) -> bool {
+        let key = match p2pk_kind(script) { Some(_) => &script[1..script.len() - 1], None => return false };
+        match sig {
+                RawSignature::Schnorr(bytes) => {
+                        let (Ok(pk), Ok(sig)) = (secp256k1::XOnlyPublicKey::from_slice(key), secp256k1::schnorr::Signature::from_slice(bytes)) else { return false; };
+                        secp.verify_schnorr(&sig, msg, &pk).is_ok()
+                }
+                RawSignature::Ecdsa(sig) => PublicKey::from_slice(key).is_ok_and(|pk| secp.verify_ecdsa(msg, sig, &pk).is_ok()),
+        }
+}
+
+fn no_matching_key(input_index: usize) -> KisrError {
+        KisrError::new(KisrErrorCode::NoMatchingKey, format!("no key matches the script of input {}", input_index)).with("inputIndex", input_index)
+}
+
+pub(crate) enum RawSignature {
+        Schnorr([u8; 64]),
+        Ecdsa(secp256k1::ecdsa::Signature),
+}
+
+// The message a key signs for `input_index`; Schnorr and ECDSA use different sighash domains
+pub(crate) fn input_message(signable: &SignableTransaction, input_index: usize, sig_type: SigHashType, ecdsa: bool, reused: &mut SigHashReusedValuesUnsync) -> Result<Message, KisrError> {
+        let sig_hash = if ecdsa {
+                calc_ecdsa_signature_hash(&signable.as_verifiable(), input_index, sig_type, reused)
+        } else {
+                calc_schnorr_signature_hash(&signable.as_verifiable(), input_index, sig_type, reused)
+        };
+        Message::from_digest_slice(&sig_hash.as_bytes()).map_err(|_| KisrError::new(KisrErrorCode::Internal, "invalid sighash"))
+}
+
+pub(crate) fn sign_message(secp: &Secp256k1<secp256k1::All>, msg: &Message, keypair: &Keypair, ecdsa: bool) -> RawSignature {
+        if ecdsa {
+                RawSignature::Ecdsa(secp.sign_ecdsa(msg, &keypair.secret_key()))
+        } else {
+                let aux = [0u8;32];
+                RawSignature::Schnorr(*secp.sign_schnorr_with_aux_rand(msg, keypair, &aux).as_ref())
+        }
+}
+
+// P2PK signature script: a single push of the signature followed by its sighash type byte
+pub(crate) fn signature_script(sig: &RawSignature, sig_type: SigHashType) -> Vec<u8> {
+        match sig {
+                RawSignature::Schnorr(sig_bytes) => {
+                        let mut sig_script = Vec::with_capacity(1 + 64 + 1);
+                        sig_script.push(64u8 + 1u8);
+                        sig_script.extend_from_slice(sig_bytes);
+                        sig_script.push(sig_type.to_u8());
+                        sig_script
+                }
+                RawSignature::Ecdsa(sig) => {
+                        let mut der = sig.serialize_der().to_vec();
+                        der.push(sig_type.to_u8());
+                        let mut sig_script: Vec<u8> = Vec::with_capacity(der.len() + 5);
+                        script_push_data(&mut sig_script, &der);
+                        sig_script
+                }
+        }
+}
+
+// Fills in the signature script of each input the plan selects and leaves the others untouched.
+// Fails naming the first selected input no key matches.
+pub(crate) fn sign_inputs(tx: &mut Transaction, entries: &[UtxoEntry], signer: &InputSigner, plan: &SignPlan) -> Result<(), KisrError> {
+        let secp = Secp256k1::new();
+        let signable = SignableTransaction::with_entries(tx.clone(), entries.to_vec());
+        let mut reused = SigHashReusedValuesUnsync::new();
+        for (input_index, entry) in entries.iter().enumerate().take(signable.tx.inputs.len()) {
+                if !plan.signs(input_index) { continue; }
+                let sig_type = plan.sig_type(input_index);
+                let ecdsa = signer.algo_for(entry).ok_or_else(|| no_matching_key(input_index))?;
+                let msg = input_message(&signable, input_index, sig_type, ecdsa, &mut reused)?;
+                let sig = signer.sign(&secp, input_index, entry, &msg, ecdsa)?;
+                tx.inputs[input_index].signature_script = signature_script(&sig, sig_type);
+        }
+        Ok(())
+}